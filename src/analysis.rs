@@ -0,0 +1,122 @@
+use crate::{
+    computer::{solve_position, GameValue},
+    history::MoveHistory,
+    transposition::TranspositionTable,
+    Board, Move, Player,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MoveLabel {
+    // keeps the value the position had
+    Best,
+    // gives away a win, but still draws
+    Inaccuracy,
+    // turns a draw or a win into a loss
+    Blunder,
+}
+
+impl MoveLabel {
+    pub fn get_name(&self) -> String {
+        match self {
+            MoveLabel::Best => "best".to_string(),
+            MoveLabel::Inaccuracy => "inaccuracy".to_string(),
+            MoveLabel::Blunder => "blunder".to_string(),
+        }
+    }
+}
+
+// what a move did to the value of the position, from the point of view of the player who made it
+#[derive(Clone, Copy)]
+pub struct MoveAnalysis {
+    pub label: MoveLabel,
+    pub before: GameValue,
+    pub after: GameValue,
+    // a move that would have kept the value, when the move played didn't
+    pub better_move: Option<Move>,
+}
+
+impl MoveAnalysis {
+    pub fn get_label(&self) -> MoveLabel {
+        self.label
+    }
+
+    pub fn get_before(&self) -> GameValue {
+        self.before
+    }
+
+    pub fn get_after(&self) -> GameValue {
+        self.after
+    }
+
+    pub fn get_better_move(&self) -> Option<Move> {
+        self.better_move
+    }
+}
+
+// Solves the position before and after every move of the game. Moves made while the
+// board was still too big to solve are left as none
+pub fn analyse_game(history: &MoveHistory) -> Vec<Option<MoveAnalysis>> {
+    let mut table = TranspositionTable::new();
+
+    history
+        .moves()
+        .iter()
+        .zip(history.boards())
+        .map(|(entry, game_board)| {
+            analyse_move(game_board, entry.get_move(), entry.get_player(), &mut table)
+        })
+        .collect()
+}
+
+fn analyse_move(
+    game_board: &Board,
+    player_move: Move,
+    player: Player,
+    table: &mut TranspositionTable,
+) -> Option<MoveAnalysis> {
+    let before = solve_position(game_board, player, table)?;
+    let after = value_after(game_board, player_move, player, table)?;
+
+    let label = if after == before {
+        MoveLabel::Best
+    } else if after == GameValue::Loss {
+        MoveLabel::Blunder
+    } else {
+        MoveLabel::Inaccuracy
+    };
+
+    let better_move = match label {
+        MoveLabel::Best => None,
+        _ => game_board
+            .moves_for(player)
+            .into_iter()
+            .find(|&other| value_after(game_board, other, player, table) == Some(before)),
+    };
+
+    Some(MoveAnalysis {
+        label,
+        before,
+        after,
+        better_move,
+    })
+}
+
+fn value_after(
+    game_board: &Board,
+    player_move: Move,
+    player: Player,
+    table: &mut TranspositionTable,
+) -> Option<GameValue> {
+    let mut next_board = game_board.clone();
+    next_board.play(player_move, player);
+
+    solve_position(&next_board, player.opponent(), table).map(|value| value.flip())
+}
+
+// The last move that turned a draw or a win into a loss for the player who made it. That
+// player never got back from it, so it's where the game was lost
+pub fn find_turning_point(analysis: &[Option<MoveAnalysis>]) -> Option<usize> {
+    analysis.iter().rposition(|result| {
+        matches!(result, Some(result) if result.get_label() == MoveLabel::Blunder)
+    })
+}
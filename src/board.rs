@@ -0,0 +1,901 @@
+use std::rc::Rc;
+
+use crate::{BoardPiece, GameOutcome, GameState, Move, Player, Position, Variant};
+
+// the directions a line can run in, as (z, y, x) steps. Only the first four stay on a
+// single layer, the rest are needed for the vertical and space diagonal lines of qubic
+const DIRECTIONS: [(i8, i8, i8); 13] = [
+    (0, 0, 1),
+    (0, 1, 0),
+    (0, 1, 1),
+    (0, 1, -1),
+    (1, 0, 0),
+    (1, 0, 1),
+    (1, 0, -1),
+    (1, 1, 0),
+    (1, -1, 0),
+    (1, 1, 1),
+    (1, 1, -1),
+    (1, -1, 1),
+    (1, -1, -1),
+];
+
+pub const SUB_BOARD_SIZE: i8 = 3;
+
+// Every stretch of win_length cells in a row on the board, which are all the lines a player
+// can win with, and for each cell the lines it is part of. Winning, the computer's threats
+// and the highlighted line on the board all come from here, so they always agree. In
+// ultimate the lines stay inside their sub-board
+#[derive(PartialEq)]
+struct LineTable {
+    lines: Vec<Vec<Position>>,
+    // indexed like the cells of the board
+    lines_by_cell: Vec<Vec<usize>>,
+    // indexed like the sub-boards of ultimate, empty on every other board
+    lines_by_sub_board: Vec<Vec<usize>>,
+    // the lines of sub-boards on the meta-board of ultimate, empty on every other board
+    meta_lines: Vec<Vec<Position>>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Board {
+    width: usize,
+    height: usize,
+    layers: usize,
+    win_length: usize,
+    variant: Variant,
+    cells: Vec<BoardPiece>,
+    last_move: Option<Position>,
+    // the cells of the pieces still on the board, oldest first
+    placement_order: Vec<Position>,
+    first_player: Player,
+    // only depends on the size and the variant of the board, so every copy of a board shares one
+    line_table: Rc<LineTable>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new(3, 3)
+    }
+}
+
+impl Board {
+    pub fn new(size: usize, win_length: usize) -> Board {
+        Board::with_dimensions(size, size, 1, win_length)
+    }
+
+    fn with_dimensions(width: usize, height: usize, layers: usize, win_length: usize) -> Board {
+        for dimension in [width, height, layers] {
+            if dimension == 0 || dimension > i8::MAX as usize {
+                panic!("Invalid board size");
+            }
+        }
+
+        if win_length == 0 || win_length > width.max(height) {
+            panic!("Invalid win length");
+        }
+
+        let mut board = Board {
+            width,
+            height,
+            layers,
+            win_length,
+            variant: Variant::Classic,
+            cells: vec![BoardPiece::Empty; width * height * layers],
+            last_move: None,
+            placement_order: Vec::new(),
+            first_player: Player::X,
+            line_table: Rc::new(LineTable {
+                lines: Vec::new(),
+                lines_by_cell: Vec::new(),
+                lines_by_sub_board: Vec::new(),
+                meta_lines: Vec::new(),
+            }),
+        };
+
+        board.line_table = Rc::new(board.build_line_table());
+        board
+    }
+
+    fn build_line_table(&self) -> LineTable {
+        let ultimate = self.variant == Variant::Ultimate;
+        let sub_boards = if ultimate { SUB_BOARD_SIZE * SUB_BOARD_SIZE } else { 0 };
+
+        let mut lines = Vec::new();
+        let mut lines_by_cell = vec![Vec::new(); self.cells.len()];
+        let mut lines_by_sub_board = vec![Vec::new(); sub_boards as usize];
+        let win_length = self.win_length as i8;
+
+        for start in self.all_cells() {
+            for &direction in self.directions() {
+                let line: Vec<Position> = (0..win_length)
+                    .map(|i| step(start, direction, i))
+                    .collect();
+
+                if !line.iter().all(|&cell| self.contains(cell)) {
+                    continue;
+                }
+
+                if ultimate {
+                    let sub_board = self.sub_board_of(start);
+
+                    if !line.iter().all(|&cell| self.sub_board_of(cell) == sub_board) {
+                        continue;
+                    }
+
+                    let sub_board_index = sub_board.get_y() * SUB_BOARD_SIZE + sub_board.get_x();
+                    lines_by_sub_board[sub_board_index as usize].push(lines.len());
+                }
+
+                for &cell in &line {
+                    lines_by_cell[self.index(cell)].push(lines.len());
+                }
+
+                lines.push(line);
+            }
+        }
+
+        // the sub-boards sit on the meta-board the way the cells sit in a sub-board, so
+        // the meta-board has the lines of the top left sub-board
+        let meta_lines = match lines_by_sub_board.first() {
+            Some(top_left) => top_left.iter().map(|&line| lines[line].clone()).collect(),
+            None => Vec::new(),
+        };
+
+        LineTable {
+            lines,
+            lines_by_cell,
+            lines_by_sub_board,
+            meta_lines,
+        }
+    }
+
+    // nine 3x3 sub-boards laid out on a 3x3 meta-board
+    pub fn ultimate() -> Board {
+        let size = (SUB_BOARD_SIZE * SUB_BOARD_SIZE) as usize;
+
+        Board::new(size, SUB_BOARD_SIZE as usize).set_variant(Variant::Ultimate)
+    }
+
+    // four 4x4 layers, lines can run through the layers as well as along them
+    pub fn qubic() -> Board {
+        Board::with_dimensions(4, 4, 4, 4).set_variant(Variant::Qubic)
+    }
+
+    // three men's morris, once both players have three pieces down they move them instead
+    pub fn morris() -> Board {
+        Board::new(3, 3).set_variant(Variant::Morris)
+    }
+
+    // pieces are dropped into a column and fall to the lowest empty cell
+    pub fn gravity(width: usize, height: usize, win_length: usize) -> Board {
+        Board::with_dimensions(width, height, 1, win_length).set_variant(Variant::Gravity)
+    }
+
+    pub fn set_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self.line_table = Rc::new(self.build_line_table());
+        self
+    }
+
+    pub fn get_variant(&self) -> Variant {
+        self.variant
+    }
+
+    // the side that makes the first move, X unless set otherwise
+    pub fn set_first_player(mut self, first_player: Player) -> Self {
+        self.first_player = first_player;
+        self
+    }
+
+    pub fn get_first_player(&self) -> Player {
+        self.first_player
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_layers(&self) -> usize {
+        self.layers
+    }
+
+    pub fn get_win_length(&self) -> usize {
+        self.win_length
+    }
+
+    pub fn rows(&self) -> Vec<Vec<BoardPiece>> {
+        self.layer_rows(0)
+    }
+
+    pub fn layer_rows(&self, layer: usize) -> Vec<Vec<BoardPiece>> {
+        let layer_size = self.width * self.height;
+
+        self.cells[layer * layer_size..(layer + 1) * layer_size]
+            .chunks(self.width)
+            .map(|row| row.to_vec())
+            .collect()
+    }
+
+    pub fn contains(&self, position: Position) -> bool {
+        (0..self.layers as i8).contains(&position.get_z())
+            && (0..self.height as i8).contains(&position.get_y())
+            && (0..self.width as i8).contains(&position.get_x())
+    }
+
+    pub fn cell(&self, position: Position) -> BoardPiece {
+        self.cells[self.index(position)]
+    }
+
+    pub fn get_turn(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|&piece| *piece != BoardPiece::Empty)
+            .count()
+    }
+
+    // The first player opens, after that the players take turns. The last piece
+    // placed can't have vanished yet, so it always tells who moved last
+    pub fn side_to_move(&self) -> Player {
+        match self.last_move.and_then(|position| self.cell(position).get_player()) {
+            Some(last_player) => last_player.opponent(),
+            None => self.first_player,
+        }
+    }
+
+    // in infinite and morris each player only has as many marks as it takes to win
+    pub fn get_mark_limit(&self) -> Option<usize> {
+        match self.variant {
+            Variant::Infinite | Variant::Morris => Some(self.win_length),
+            _ => None,
+        }
+    }
+
+    // in morris a player with all of their pieces down moves them instead of placing more
+    pub fn in_movement_phase(&self, player: Player) -> bool {
+        self.variant == Variant::Morris
+            && self.count_pieces(player.get_board_piece()) >= self.win_length
+    }
+
+    // the piece that will be taken off the board when the side to move places its next one
+    pub fn next_to_vanish(&self) -> Option<Position> {
+        self.oldest_piece_to_remove(self.side_to_move())
+    }
+
+    fn oldest_piece_to_remove(&self, player: Player) -> Option<Position> {
+        if self.variant != Variant::Infinite {
+            return None;
+        }
+
+        let mark_limit = self.get_mark_limit()?;
+        let piece = player.get_board_piece();
+
+        if self.count_pieces(piece) < mark_limit {
+            return None;
+        }
+
+        self.placement_order
+            .iter()
+            .copied()
+            .find(|&position| self.cell(position) == piece)
+    }
+
+    pub fn get_last_move(&self) -> Option<Position> {
+        self.last_move
+    }
+
+    pub fn valid_move(&self, position: Position) -> bool {
+        // with gravity only the column matters, the piece falls as far as it can
+        if self.variant == Variant::Gravity {
+            return self.contains(position) && self.drop_position(position.get_x()).is_some();
+        }
+
+        if !self.contains(position) || self.cell(position) != BoardPiece::Empty {
+            return false;
+        }
+
+        if self.in_movement_phase(self.side_to_move()) {
+            return false;
+        }
+
+        if self.variant == Variant::Ultimate {
+            let sub_board = self.sub_board_of(position);
+
+            if self.sub_board_outcome(sub_board) != GameState::Running {
+                return false;
+            }
+
+            if let Some(active) = self.active_sub_board() {
+                return sub_board == active;
+            }
+        }
+
+        true
+    }
+
+    pub fn empty_cells(&self) -> Vec<Position> {
+        self.all_cells()
+            .into_iter()
+            .filter(|&position| self.cell(position) == BoardPiece::Empty)
+            .collect()
+    }
+
+    // the lowest empty cell of a column, or None once the column is full
+    pub fn drop_position(&self, column: i8) -> Option<Position> {
+        (0..self.height as i8)
+            .rev()
+            .map(|y| Position::new(y, column))
+            .find(|&position| self.contains(position) && self.cell(position) == BoardPiece::Empty)
+    }
+
+    // the cell a piece played at position ends up in
+    pub fn landing_position(&self, position: Position) -> Position {
+        match self.variant {
+            Variant::Gravity => self.drop_position(position.get_x()).unwrap_or(position),
+            _ => position,
+        }
+    }
+
+    // every move the side to move can make, placements or slides
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let side_to_move = self.side_to_move();
+
+        if self.in_movement_phase(side_to_move) {
+            return self.legal_slides(side_to_move);
+        }
+
+        self.legal_placements()
+            .into_iter()
+            .map(Move::place)
+            .collect()
+    }
+
+    // the cells the side to move is allowed to place a piece in
+    pub fn legal_placements(&self) -> Vec<Position> {
+        if self.variant == Variant::Gravity {
+            return (0..self.width as i8)
+                .filter_map(|column| self.drop_position(column))
+                .collect();
+        }
+
+        self.empty_cells()
+            .into_iter()
+            .filter(|&position| self.valid_move(position))
+            .collect()
+    }
+
+    // the sub-board (in meta-board coordinates) that a cell belongs to
+    pub fn sub_board_of(&self, position: Position) -> Position {
+        Position::new(
+            position.get_y() / SUB_BOARD_SIZE,
+            position.get_x() / SUB_BOARD_SIZE,
+        )
+    }
+
+    // The square the last move was made in picks the sub-board the next move has to be
+    // made in. If that sub-board is already decided the next move can go anywhere
+    pub fn active_sub_board(&self) -> Option<Position> {
+        if self.variant != Variant::Ultimate {
+            return None;
+        }
+
+        let last_move = self.last_move?;
+        let target = Position::new(
+            last_move.get_y() % SUB_BOARD_SIZE,
+            last_move.get_x() % SUB_BOARD_SIZE,
+        );
+
+        if self.sub_board_outcome(target) == GameState::Running {
+            Some(target)
+        } else {
+            None
+        }
+    }
+
+    pub fn sub_board_outcome(&self, sub_board: Position) -> GameState {
+        let top = sub_board.get_y() * SUB_BOARD_SIZE;
+        let left = sub_board.get_x() * SUB_BOARD_SIZE;
+        let sub_board_index = (sub_board.get_y() * SUB_BOARD_SIZE + sub_board.get_x()) as usize;
+
+        for &line in &self.line_table.lines_by_sub_board[sub_board_index] {
+            let line = &self.line_table.lines[line];
+
+            let first = self.cell(line[0]);
+            if let Some(winner) = first.get_player() {
+                if line.iter().all(|&position| self.cell(position) == first) {
+                    return GameState::Win(GameOutcome::new(winner, line.clone()));
+                }
+            }
+        }
+
+        for y in top..top + SUB_BOARD_SIZE {
+            for x in left..left + SUB_BOARD_SIZE {
+                if self.cell(Position::new(y, x)) == BoardPiece::Empty {
+                    return GameState::Running;
+                }
+            }
+        }
+
+        GameState::Draw
+    }
+
+    pub fn sub_board_winner(&self, sub_board: Position) -> Option<Player> {
+        match self.sub_board_outcome(sub_board) {
+            GameState::Win(outcome) => Some(outcome.get_winner()),
+            _ => None,
+        }
+    }
+
+    pub fn legal_slides(&self, player: Player) -> Vec<Move> {
+        let mut slides = Vec::new();
+
+        for from in self.all_cells() {
+            for to in self.all_cells() {
+                if self.can_slide(player, from, to) {
+                    slides.push(Move::slide(from, to));
+                }
+            }
+        }
+
+        slides
+    }
+
+    // the moves player could make right now if it were their turn
+    pub fn moves_for(&self, player: Player) -> Vec<Move> {
+        if self.in_movement_phase(player) {
+            self.legal_slides(player)
+        } else {
+            self.legal_placements().into_iter().map(Move::place).collect()
+        }
+    }
+
+    // the moves that would win the game for player straight away
+    pub fn winning_moves(&self, player: Player) -> Vec<Move> {
+        self.moves_for(player)
+            .into_iter()
+            .filter(|&player_move| self.wins_with(player_move, player))
+            .collect()
+    }
+
+    // player has more than one way to win on their next move, and only one can be blocked
+    pub fn is_fork(&self, player: Player) -> bool {
+        self.winning_moves(player).len() >= 2
+    }
+
+    // the moves that would leave player with a fork
+    pub fn fork_moves(&self, player: Player) -> Vec<Move> {
+        self.moves_for(player)
+            .into_iter()
+            .filter(|&player_move| {
+                let mut next_board = self.clone();
+                next_board.play(player_move, player);
+
+                next_board.is_fork(player)
+            })
+            .collect()
+    }
+
+    // Where a placement can only complete lines through its own cell, and nothing else
+    // changes on the board, the lines through it are enough to tell if it wins
+    fn wins_with(&self, player_move: Move, player: Player) -> bool {
+        if let (Variant::Classic | Variant::Gravity | Variant::Qubic, None) =
+            (self.variant, player_move.get_from())
+        {
+            let position = player_move.get_to();
+            let piece = player.get_board_piece();
+
+            return self.lines_through(position).iter().any(|line| {
+                line.iter()
+                    .all(|&cell| cell == position || self.cell(cell) == piece)
+            });
+        }
+
+        let mut next_board = self.clone();
+        next_board.play(player_move, player);
+
+        match next_board.outcome() {
+            GameState::Win(outcome) => outcome.get_winner() == player,
+            _ => false,
+        }
+    }
+
+    pub fn valid_slide(&self, from: Position, to: Position) -> bool {
+        self.can_slide(self.side_to_move(), from, to)
+    }
+
+    fn can_slide(&self, player: Player, from: Position, to: Position) -> bool {
+        self.in_movement_phase(player)
+            && self.contains(from)
+            && self.contains(to)
+            && self.cell(from) == player.get_board_piece()
+            && self.cell(to) == BoardPiece::Empty
+            && self.adjacent(from, to)
+    }
+
+    // Pieces move along the lines of the board, which are the rows, the columns and the two
+    // long diagonals. A diagonal step is only allowed along one of the long diagonals
+    fn adjacent(&self, from: Position, to: Position) -> bool {
+        let step_y = (to.get_y() - from.get_y()).abs();
+        let step_x = (to.get_x() - from.get_x()).abs();
+
+        if from.get_z() != to.get_z() || step_y > 1 || step_x > 1 || step_y + step_x == 0 {
+            return false;
+        }
+
+        if step_y + step_x == 1 {
+            return true;
+        }
+
+        let last = self.width as i8 - 1;
+        let on_diagonal = |position: Position| position.get_y() == position.get_x();
+        let on_anti_diagonal = |position: Position| position.get_y() + position.get_x() == last;
+
+        (on_diagonal(from) && on_diagonal(to)) || (on_anti_diagonal(from) && on_anti_diagonal(to))
+    }
+
+    pub fn play(&mut self, player_move: Move, player: Player) {
+        match player_move.get_from() {
+            Some(from) => self.slide(from, player_move.get_to(), player),
+            None => self.place(player_move.get_to(), player),
+        }
+    }
+
+    pub fn slide(&mut self, from: Position, to: Position, player: Player) {
+        if !self.can_slide(player, from, to) {
+            panic!("Invalid move");
+        }
+
+        let from_index = self.index(from);
+        let to_index = self.index(to);
+
+        self.cells[from_index] = BoardPiece::Empty;
+        self.cells[to_index] = player.get_board_piece();
+        self.last_move = Some(to);
+
+        for placed in self.placement_order.iter_mut() {
+            if *placed == from {
+                *placed = to;
+            }
+        }
+    }
+
+    // only real players can be placed, selection markers are a display concern
+    pub fn place(&mut self, position: Position, player: Player) {
+        if !self.valid_move(position) {
+            panic!("Invalid move");
+        }
+
+        let position = self.landing_position(position);
+
+        if let Some(oldest) = self.oldest_piece_to_remove(player) {
+            let oldest_index = self.index(oldest);
+            self.cells[oldest_index] = BoardPiece::Empty;
+            self.placement_order.retain(|&placed| placed != oldest);
+        }
+
+        let index = self.index(position);
+        self.cells[index] = player.get_board_piece();
+        self.last_move = Some(position);
+        self.placement_order.push(position);
+    }
+
+    pub fn winning_lines(&self) -> &[Vec<Position>] {
+        &self.line_table.lines
+    }
+
+    // every stretch of win_length cells that passes through position
+    pub fn lines_through(&self, position: Position) -> Vec<&[Position]> {
+        self.line_table.lines_by_cell[self.index(position)]
+            .iter()
+            .map(|&line| self.line_table.lines[line].as_slice())
+            .collect()
+    }
+
+    // the other cells that share at least one line with position, each of them once
+    pub fn cells_in_lines_through(&self, position: Position) -> Vec<Position> {
+        let mut cells = Vec::new();
+
+        for line in self.lines_through(position) {
+            for &cell in line {
+                if cell != position && !cells.contains(&cell) {
+                    cells.push(cell);
+                }
+            }
+        }
+
+        cells
+    }
+
+    pub fn outcome(&self) -> GameState {
+        if self.variant == Variant::Ultimate {
+            return self.ultimate_outcome();
+        }
+
+        for line in self.winning_lines() {
+            let first = self.cell(line[0]);
+
+            let line_owner = match first.get_player() {
+                Some(player) => player,
+                None => continue,
+            };
+
+            if line.iter().all(|&position| self.cell(position) == first) {
+                let winner = match self.variant {
+                    Variant::Misere => line_owner.opponent(),
+                    _ => line_owner,
+                };
+
+                return GameState::Win(GameOutcome::new(winner, line.clone()));
+            }
+        }
+
+        // pieces keep vanishing in infinite, so the board never fills up for a draw
+        if self.variant == Variant::Infinite {
+            return GameState::Running;
+        }
+
+        // in morris a player who can't move any of their pieces loses
+        if self.variant == Variant::Morris {
+            let side_to_move = self.side_to_move();
+
+            if self.legal_moves().is_empty() {
+                return GameState::Win(GameOutcome::new(side_to_move.opponent(), Vec::new()));
+            }
+
+            return GameState::Running;
+        }
+
+        if self.legal_moves().is_empty() {
+            GameState::Draw
+        } else {
+            GameState::Running
+        }
+    }
+
+    // three won sub-boards in a row on the meta-board win the game,
+    // the winning line is every cell of those sub-boards
+    fn ultimate_outcome(&self) -> GameState {
+        for line in &self.line_table.meta_lines {
+            let winners: Vec<Option<Player>> = line
+                .iter()
+                .map(|&sub_board| self.sub_board_winner(sub_board))
+                .collect();
+
+            if let Some(winner) = winners[0] {
+                if winners.iter().all(|&sub_board_winner| sub_board_winner == Some(winner)) {
+                    let mut cells = Vec::new();
+
+                    for sub_board in line {
+                        let (sub_y, sub_x) = (sub_board.get_y(), sub_board.get_x());
+
+                        for y in 0..SUB_BOARD_SIZE {
+                            for x in 0..SUB_BOARD_SIZE {
+                                cells.push(Position::new(
+                                    sub_y * SUB_BOARD_SIZE + y,
+                                    sub_x * SUB_BOARD_SIZE + x,
+                                ));
+                            }
+                        }
+                    }
+
+                    return GameState::Win(GameOutcome::new(winner, cells));
+                }
+            }
+        }
+
+        if self.legal_moves().is_empty() {
+            GameState::Draw
+        } else {
+            GameState::Running
+        }
+    }
+
+    fn all_cells(&self) -> Vec<Position> {
+        let mut positions = Vec::new();
+
+        for z in 0..self.layers as i8 {
+            for y in 0..self.height as i8 {
+                for x in 0..self.width as i8 {
+                    positions.push(Position::new_3d(z, y, x));
+                }
+            }
+        }
+
+        positions
+    }
+
+    // lines on a flat board can't leave the only layer
+    fn directions(&self) -> &'static [(i8, i8, i8)] {
+        if self.layers > 1 {
+            &DIRECTIONS
+        } else {
+            &DIRECTIONS[..4]
+        }
+    }
+
+    fn index(&self, position: Position) -> usize {
+        (position.get_z() as usize * self.height + position.get_y() as usize) * self.width
+            + position.get_x() as usize
+    }
+
+    fn count_pieces(&self, piece: BoardPiece) -> usize {
+        self.cells
+            .iter()
+            .filter(|&board_piece| *board_piece == piece)
+            .count()
+    }
+}
+
+fn step(position: Position, (step_z, step_y, step_x): (i8, i8, i8), distance: i8) -> Position {
+    Position::new_3d(
+        position.get_z() + distance * step_z,
+        position.get_y() + distance * step_y,
+        position.get_x() + distance * step_x,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ultimate_lines_stay_in_their_sub_board() {
+        let game_board = Board::ultimate();
+        let sub_boards = (SUB_BOARD_SIZE * SUB_BOARD_SIZE) as usize;
+
+        assert_eq!(game_board.winning_lines().len(), sub_boards * 8);
+        assert_eq!(game_board.line_table.meta_lines.len(), 8);
+
+        for line in game_board.winning_lines() {
+            let sub_board = game_board.sub_board_of(line[0]);
+            assert!(line.iter().all(|&cell| game_board.sub_board_of(cell) == sub_board));
+        }
+    }
+
+    #[test]
+    fn ultimate_sub_board_is_won_inside_its_edges() {
+        let mut game_board = Board::ultimate();
+
+        // the pieces are put down directly, which sub-board is next to play doesn't matter here
+        let put_x = |game_board: &mut Board, x: i8| {
+            let index = game_board.index(Position::new(0, x));
+            game_board.cells[index] = BoardPiece::X;
+        };
+
+        // a row of three that runs over the edge between the top left and top middle sub-boards
+        for x in 1..4 {
+            put_x(&mut game_board, x);
+        }
+        assert!(game_board.sub_board_winner(Position::new(0, 0)).is_none());
+        assert!(game_board.sub_board_winner(Position::new(0, 1)).is_none());
+
+        put_x(&mut game_board, 0);
+        assert!(game_board.sub_board_winner(Position::new(0, 0)) == Some(Player::X));
+    }
+
+    #[test]
+    fn four_in_a_row_wins_on_six_by_six() {
+        let mut game_board = Board::new(6, 4);
+
+        for x in 1..4 {
+            game_board.place(Position::new(2, x), Player::X);
+            game_board.place(Position::new(5, x), Player::O);
+        }
+        assert!(game_board.outcome() == GameState::Running);
+
+        game_board.place(Position::new(2, 4), Player::X);
+        match game_board.outcome() {
+            GameState::Win(outcome) => {
+                assert!(outcome.get_winner() == Player::X);
+                assert_eq!(outcome.get_line().len(), 4);
+            }
+            _ => panic!("Four in a row should win"),
+        }
+    }
+
+    #[test]
+    fn qubic_has_76_lines_and_space_diagonals_win() {
+        let mut game_board = Board::qubic();
+
+        assert_eq!(game_board.winning_lines().len(), 76);
+
+        for i in 0..4 {
+            game_board.place(Position::new_3d(i, i, 3 - i), Player::O);
+        }
+
+        match game_board.outcome() {
+            GameState::Win(outcome) => assert!(outcome.get_winner() == Player::O),
+            _ => panic!("A space diagonal should win"),
+        }
+    }
+
+    #[test]
+    fn infinite_removes_the_oldest_mark() {
+        let mut game_board = Board::new(3, 3).set_variant(Variant::Infinite);
+
+        for (y, x) in [(0, 0), (1, 0), (0, 2), (1, 2), (2, 1), (2, 0)] {
+            let side_to_move = game_board.side_to_move();
+            game_board.place(Position::new(y, x), side_to_move);
+        }
+
+        assert!(game_board.next_to_vanish() == Some(Position::new(0, 0)));
+        game_board.place(Position::new(1, 1), Player::X);
+
+        assert!(game_board.cell(Position::new(0, 0)) == BoardPiece::Empty);
+        assert_eq!(game_board.count_pieces(BoardPiece::X), 3);
+        assert!(game_board.next_to_vanish() == Some(Position::new(1, 0)));
+    }
+
+    #[test]
+    fn infinite_never_ends_in_a_draw() {
+        let mut game_board = Board::new(3, 3).set_variant(Variant::Infinite);
+
+        // keeps away from winning moves for as long as it can
+        for _ in 0..100 {
+            let side_to_move = game_board.side_to_move();
+            let placements = game_board.legal_placements();
+            let position = placements
+                .iter()
+                .copied()
+                .find(|&position| !game_board.wins_with(Move::place(position), side_to_move))
+                .unwrap_or(placements[0]);
+
+            game_board.place(position, side_to_move);
+
+            match game_board.outcome() {
+                GameState::Running => {}
+                GameState::Draw => panic!("Infinite should never end in a draw"),
+                GameState::Win(_) => break,
+            }
+        }
+    }
+
+    #[test]
+    fn morris_slides_only_along_lines() {
+        let mut game_board = Board::morris();
+
+        // x: (0, 1), (2, 0), (2, 2) and o: (1, 1), (0, 0), (0, 2), x to move
+        for (y, x) in [(0, 1), (1, 1), (2, 0), (0, 0), (2, 2), (0, 2)] {
+            let side_to_move = game_board.side_to_move();
+            game_board.place(Position::new(y, x), side_to_move);
+        }
+
+        assert!(game_board.in_movement_phase(Player::X));
+
+        // (0, 1) isn't on a long diagonal, so it can't step diagonally
+        assert!(!game_board.valid_slide(Position::new(0, 1), Position::new(1, 0)));
+        assert!(!game_board.valid_slide(Position::new(0, 1), Position::new(1, 2)));
+        assert!(!game_board.valid_slide(Position::new(2, 0), Position::new(0, 0)));
+        assert!(game_board.valid_slide(Position::new(2, 0), Position::new(1, 0)));
+        assert!(game_board.valid_slide(Position::new(2, 2), Position::new(1, 2)));
+    }
+
+    #[test]
+    fn morris_side_without_slides_loses() {
+        let mut game_board = Board::morris();
+
+        // filled in directly, a full board without a line leaves x to move and nowhere to go
+        let rows = ["XOX", "XOO", "OXX"];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, piece) in row.chars().enumerate() {
+                let index = game_board.index(Position::new(y as i8, x as i8));
+                game_board.cells[index] = match piece {
+                    'X' => BoardPiece::X,
+                    _ => BoardPiece::O,
+                };
+            }
+        }
+
+        assert!(game_board.side_to_move() == Player::X);
+        assert!(game_board.legal_slides(Player::X).is_empty());
+
+        match game_board.outcome() {
+            GameState::Win(outcome) => assert!(outcome.get_winner() == Player::O),
+            _ => panic!("A side that can't slide should lose"),
+        }
+    }
+}
@@ -1,6 +1,21 @@
-use rand::Rng;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
 
-use crate::{valid_move, Board, BoardPiece, Column, Diagonal, Difficulty, Position, Row};
+use crate::{
+    board::SUB_BOARD_SIZE,
+    rng::GameRng,
+    tablebase::tablebase,
+    transposition::{canonical_hash, Bound, TableEntry, TranspositionTable},
+    Board, BoardPiece, Difficulty, GameState, Move, Player, Position, Variant,
+};
+
+// the top of the strength scale, where the computer always plays the best move it can find
+pub const MAX_STRENGTH: u8 = 100;
+
+// how far the adaptive difficulty's strength moves after each game the human wins or loses
+pub const ADAPTIVE_STEP: u8 = 10;
 
 #[derive(Clone, Copy)]
 pub struct ComputerMove {
@@ -33,240 +48,1216 @@ impl ComputerMove {
     }
 }
 
-pub fn computer_move(game_board: &[[BoardPiece; 3]; 3], difficulty: Difficulty) -> Position {
+// a move suggested to the human, and a short reason for it
+pub struct Hint {
+    pub player_move: Move,
+    pub reason: String,
+}
+
+impl Hint {
+    pub fn new(player_move: Move, reason: String) -> Hint {
+        Hint {
+            player_move,
+            reason,
+        }
+    }
+
+    pub fn get_move(&self) -> Move {
+        self.player_move
+    }
+
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+// The strongest move for player, which is the move hard would play. On classic boards a
+// win or a block on this turn is picked up right away without searching
+pub fn find_hint(
+    game_board: &Board,
+    player: Player,
+    table: &mut TranspositionTable,
+    rng: &mut GameRng,
+) -> Hint {
+    let immediate = match game_board.get_variant() {
+        Variant::Classic => [player, player.opponent()]
+            .iter()
+            .map(|side| counter_player_win(game_board, side.get_board_piece()))
+            .find(|win| win.is_valid()),
+        _ => None,
+    };
+
+    let player_move = match immediate {
+        Some(win) => Move::place(win.get_position()),
+        None => computer_move(game_board, player, Difficulty::Hard, table, rng),
+    };
+
+    Hint::new(player_move, hint_reason(game_board, player_move, player))
+}
+
+fn hint_reason(game_board: &Board, player_move: Move, player: Player) -> String {
+    if game_board.get_variant() == Variant::Misere {
+        return misere_hint_reason(game_board, player_move, player);
+    }
+
+    if wins_with_move(game_board, player_move, player) {
+        return "wins now".to_string();
+    }
+
+    let opponent = player.opponent();
+    let to = player_move.get_to();
+
+    if player_move.get_from().is_none() {
+        if let Some(line) = line_finished_by(game_board, to, opponent) {
+            return format!(
+                "blocks {}'s {}",
+                opponent.get_player_piece(),
+                describe_line(line)
+            );
+        }
+
+        if wins_with_move(game_board, player_move, opponent) {
+            return format!("blocks {}'s win", opponent.get_player_piece());
+        }
+
+        if game_board.fork_moves(opponent).contains(&player_move) {
+            return format!("blocks {}'s fork", opponent.get_player_piece());
+        }
+    }
+
+    let mut next_board = game_board.clone();
+    next_board.play(player_move, player);
+
+    if next_board.is_fork(player) {
+        return "creates a fork".to_string();
+    }
+
+    let height = game_board.get_height() as i8;
+    let width = game_board.get_width() as i8;
+
+    if height % 2 == 1 && width % 2 == 1 && to == Position::new(height / 2, width / 2) {
+        return "takes the centre".to_string();
+    }
+
+    "the strongest move the computer can find".to_string()
+}
+
+// In misere completing a line loses, so there's nothing to block. The reasons are about
+// staying out of lines of your own and leaving the opponent no way to do the same
+fn misere_hint_reason(game_board: &Board, player_move: Move, player: Player) -> String {
+    let opponent = player.opponent();
+    let to = player_move.get_to();
+
+    if line_finished_by(game_board, to, player).is_some() {
+        return "every move completes one of your lines".to_string();
+    }
+
+    let mut next_board = game_board.clone();
+    next_board.play(player_move, player);
+
+    let opponent_moves = next_board.legal_placements();
+    if next_board.outcome() == GameState::Running
+        && opponent_moves
+            .iter()
+            .all(|&position| line_finished_by(&next_board, position, opponent).is_some())
+    {
+        return format!("leaves {} only moves that complete a line", opponent.get_player_piece());
+    }
+
+    let avoided_line = game_board
+        .legal_placements()
+        .into_iter()
+        .find_map(|position| line_finished_by(game_board, position, player));
+
+    if let Some(line) = avoided_line {
+        return format!("avoids completing your {}", describe_line(line));
+    }
+
+    let height = game_board.get_height() as i8;
+    let width = game_board.get_width() as i8;
+
+    if height % 2 == 1 && width % 2 == 1 && to == Position::new(height / 2, width / 2) {
+        return "takes the centre".to_string();
+    }
+
+    "the strongest move the computer can find".to_string()
+}
+
+// the line through position that player would complete by playing there, lines are only
+// looked at on boards where they can run anywhere, so not in ultimate
+fn line_finished_by(
+    game_board: &Board,
+    position: Position,
+    player: Player,
+) -> Option<&[Position]> {
+    if game_board.get_variant() == Variant::Ultimate {
+        return None;
+    }
+
+    game_board.lines_through(position).into_iter().find(|line| {
+        line.iter()
+            .all(|&cell| cell == position || game_board.cell(cell) == player.get_board_piece())
+    })
+}
+
+fn describe_line(line: &[Position]) -> &'static str {
+    let (first, second) = (line[0], line[1]);
+
+    if first.get_z() != second.get_z() {
+        "line through the layers"
+    } else if first.get_y() == second.get_y() {
+        "row"
+    } else if first.get_x() == second.get_x() {
+        "column"
+    } else {
+        "diagonal"
+    }
+}
+
+// the table holds the positions hard mode has already searched, and is reused from move to move
+pub fn computer_move(
+    game_board: &Board,
+    player: Player,
+    difficulty: Difficulty,
+    table: &mut TranspositionTable,
+    rng: &mut GameRng,
+) -> Move {
+    graded_move(game_board, player, difficulty.get_strength(), table, rng)
+}
+
+fn difficulty_move(
+    game_board: &Board,
+    player: Player,
+    difficulty: Difficulty,
+    table: &mut TranspositionTable,
+    rng: &mut GameRng,
+) -> Move {
+    if game_board.in_movement_phase(player) {
+        return morris_computer_move(game_board, player, difficulty, table, rng);
+    }
+
+    Move::place(computer_placement(
+        game_board, player, difficulty, table, rng,
+    ))
+}
+
+// Easy, medium and hard are 0, 50 and 100 on one scale of strength. Above medium's
+// strength hard's move is played with a chance that grows to always at 100. Otherwise a
+// move is picked from all the legal moves, ranked with medium's move first and the rest
+// from the most to the least promising. At medium's strength the top of the ranking is
+// always picked, and the weaker the computer the more evenly the pick spreads over the
+// rest, until at 0 every move is as likely as easy's
+fn graded_move(
+    game_board: &Board,
+    player: Player,
+    strength: u8,
+    table: &mut TranspositionTable,
+    rng: &mut GameRng,
+) -> Move {
+    let strength = strength.min(MAX_STRENGTH);
+    let medium = Difficulty::Medium.get_strength();
+
+    let hard_share = strength.saturating_sub(medium);
+    if hard_share > 0 && rng.gen_range(0..MAX_STRENGTH - medium) < hard_share {
+        return difficulty_move(game_board, player, Difficulty::Hard, table, rng);
+    }
+
+    let mut moves = game_board.moves_for(player);
+
+    if strength == 0 {
+        return moves[rng.gen_range(0..moves.len())];
+    }
+
+    let medium_move = difficulty_move(game_board, player, Difficulty::Medium, table, rng);
+
+    // a stable sort, so moves that score the same keep the board's order
+    moves.retain(|&player_move| player_move != medium_move);
+    moves.sort_by_key(|&player_move| -move_promise(game_board, player_move, player));
+    moves.insert(0, medium_move);
+
+    // each move down the ranking is picked this many times less often than the one above
+    // it, nothing below the top at medium's strength and no less than the top at 0
+    let decay = 1.0 - strength.min(medium) as f64 / medium as f64;
+    let weights = (0..moves.len()).map(|rank| decay.powi(rank as i32));
+    let ranking = WeightedIndex::new(weights).expect("Invalid move weights");
+
+    moves[ranking.sample(rng)]
+}
+
+// how good a move looks without searching, judged by the lines through where it lands
+fn move_promise(game_board: &Board, player_move: Move, player: Player) -> i32 {
+    let score = score_cell_lines(game_board, player_move.get_to(), player);
+
+    // in misere building up lines, and keeping the opponent from theirs, is what loses
+    match game_board.get_variant() {
+        Variant::Misere => -score,
+        _ => score,
+    }
+}
+
+fn make_fork(game_board: &Board, player: Player) -> Option<Move> {
+    game_board.fork_moves(player).first().copied()
+}
+
+// Takes a fork square that leaves the opponent without any other. Failing that, a threat
+// forces the opponent to block it somewhere that doesn't give them a fork, and if there's
+// no such threat either one of the fork squares is taken anyway
+fn block_fork(game_board: &Board, player: Player) -> Option<Move> {
+    let opponent = player.opponent();
+    let forks = game_board.fork_moves(opponent);
+
+    if forks.is_empty() {
+        return None;
+    }
+
+    let leaves_no_fork = |player_move: Move, forced_block: Option<Move>| {
+        let mut next_board = game_board.clone();
+        next_board.play(player_move, player);
+
+        if let Some(forced_block) = forced_block {
+            next_board.play(forced_block, opponent);
+        }
+
+        next_board.fork_moves(opponent).is_empty()
+    };
+
+    if let Some(&fork) = forks.iter().find(|&&fork| leaves_no_fork(fork, None)) {
+        return Some(fork);
+    }
+
+    let forcing_move = game_board.moves_for(player).into_iter().find(|&player_move| {
+        let mut next_board = game_board.clone();
+        next_board.play(player_move, player);
+
+        match next_board.winning_moves(player)[..] {
+            [forced_block] => leaves_no_fork(player_move, Some(forced_block)),
+            _ => false,
+        }
+    });
+
+    forcing_move.or(forks.first().copied())
+}
+
+fn computer_placement(
+    game_board: &Board,
+    player: Player,
+    difficulty: Difficulty,
+    table: &mut TranspositionTable,
+    rng: &mut GameRng,
+) -> Position {
+    match game_board.get_variant() {
+        Variant::Misere => {
+            return misere_computer_move(game_board, player, difficulty, table, rng)
+        }
+        Variant::Ultimate => return ultimate_computer_move(game_board, player, difficulty, rng),
+        Variant::Qubic | Variant::Gravity | Variant::Infinite | Variant::Morris => {
+            return line_scoring_computer_move(game_board, player, difficulty, rng)
+        }
+        Variant::Classic => {}
+    }
+
     match difficulty {
-        Difficulty::Easy => return make_random_move(&game_board),
-        Difficulty::Medium | Difficulty::Hard => {
-            let computer_win = counter_player_win(&game_board, BoardPiece::O);
+        Difficulty::Easy => make_random_move(game_board, rng),
+        Difficulty::Medium => {
+            let computer_win = counter_player_win(game_board, player.get_board_piece());
             if computer_win.is_valid() {
                 return computer_win.get_position();
             }
 
-            let player_win = counter_player_win(&game_board, BoardPiece::X);
+            let player_win = counter_player_win(game_board, player.opponent().get_board_piece());
             if player_win.is_valid() {
                 return player_win.get_position();
             }
 
-            if difficulty == Difficulty::Medium {
-                return make_random_move(&game_board);
-            } else {
-                return find_best_move_hard_mode(&game_board).get_position();
+            if let Some(fork) = make_fork(game_board, player) {
+                return fork.get_to();
+            }
+
+            if let Some(fork_block) = block_fork(game_board, player) {
+                return fork_block.get_to();
             }
+
+            make_random_move(game_board, rng)
         }
+        // strength and adaptive play hard's moves when they play the best one
+        _ => find_best_move_hard_mode(game_board, player, table),
     }
 }
 
-fn find_best_move_hard_mode(game_board: &[[BoardPiece; 3]; 3]) -> ComputerMove {
-    // If the center is available, take it
-    if valid_move(game_board, Position::new(1, 1)) {
-        return ComputerMove::new(true, Position::new(1, 1));
+// Once all of its pieces are down in morris the computer takes a winning slide if it has one,
+// and otherwise stays away from slides that open up a winning slide for the player. Hard
+// searches the slides that are left for the one that does best a number of moves ahead
+fn morris_computer_move(
+    game_board: &Board,
+    player: Player,
+    difficulty: Difficulty,
+    table: &mut TranspositionTable,
+    rng: &mut GameRng,
+) -> Move {
+    let slides = game_board.legal_slides(player);
+
+    if difficulty == Difficulty::Easy {
+        return slides[rng.gen_range(0..slides.len())];
     }
 
-    // If the player has taken opposite corners, take an available side
-    if player_has_opposite_corners(game_board) {
-        let side_positions = vec![
-            Position::new(0, 1),
-            Position::new(1, 0),
-            Position::new(1, 2),
-            Position::new(2, 1),
-        ];
+    if let Some(&winning_slide) = slides
+        .iter()
+        .find(|&&slide| wins_with_move(game_board, slide, player))
+    {
+        return winning_slide;
+    }
 
-        for pos in side_positions {
-            if valid_move(game_board, pos) {
-                return ComputerMove::new(true, pos);
-            }
+    let safe_slides: Vec<Move> = slides
+        .iter()
+        .copied()
+        .filter(|&slide| !gives_away_winning_slide(game_board, slide, player))
+        .collect();
+
+    // every slide loses, so any of them will do
+    let candidates = if safe_slides.is_empty() {
+        slides
+    } else {
+        safe_slides
+    };
+
+    if difficulty == Difficulty::Medium {
+        return candidates[rng.gen_range(0..candidates.len())];
+    }
+
+    find_best_slide(game_board, player, candidates, table)
+}
+
+// The movement phase only has a few thousand positions, but pieces can slide back and
+// forth forever, so the search stops after a fixed number of moves and counts a game that
+// hasn't been decided by then as even
+fn find_best_slide(
+    game_board: &Board,
+    player: Player,
+    candidates: Vec<Move>,
+    table: &mut TranspositionTable,
+) -> Move {
+    let mut best = candidates[0];
+    let mut alpha = -WIN_SCORE - 1;
+
+    for slide in candidates {
+        let mut next_board = game_board.clone();
+        next_board.play(slide, player);
+
+        let score = if completed_line_through(&next_board, slide.get_to()) {
+            completed_line_score(game_board, 1)
+        } else {
+            -negamax(
+                &next_board,
+                player.opponent(),
+                MORRIS_SEARCH_DEPTH - 1,
+                -WIN_SCORE - 1,
+                -alpha,
+                1,
+                table,
+            )
+        };
+
+        if score > alpha {
+            best = slide;
+            alpha = score;
         }
     }
 
-    // If the player has taken a corner, take the opposite corner
-    let player_corners = vec![
-        Position::new(0, 0),
-        Position::new(0, 2),
-        Position::new(2, 0),
-        Position::new(2, 2),
-    ];
+    best
+}
 
-    for corner in player_corners {
-        if game_board[corner.get_y() as usize][corner.get_x() as usize] == BoardPiece::X {
-            let opposite_corner = get_opposite_corner(corner);
-            if valid_move(game_board, opposite_corner) {
-                return ComputerMove::new(true, opposite_corner);
-            }
+fn wins_with_move(game_board: &Board, player_move: Move, player: Player) -> bool {
+    let mut next_board = game_board.clone();
+    next_board.play(player_move, player);
+
+    matches!(next_board.outcome(), GameState::Win(outcome) if outcome.get_winner() == player)
+}
+
+fn gives_away_winning_slide(game_board: &Board, slide: Move, player: Player) -> bool {
+    let mut next_board = game_board.clone();
+    next_board.play(slide, player);
+
+    if let GameState::Win(_) = next_board.outcome() {
+        return true;
+    }
+
+    next_board
+        .legal_slides(player.opponent())
+        .iter()
+        .any(|&reply| wins_with_move(&next_board, reply, player.opponent()))
+}
+
+// In misere the aim is to avoid completing a line, so instead of winning and blocking
+// the computer steers clear of cells that would finish one of its own lines, and leaves
+// the cells that would finish the player's lines open for the player to be forced into
+fn misere_computer_move(
+    game_board: &Board,
+    player: Player,
+    difficulty: Difficulty,
+    table: &mut TranspositionTable,
+    rng: &mut GameRng,
+) -> Position {
+    if difficulty == Difficulty::Easy {
+        return make_random_move(game_board, rng);
+    }
+
+    // once the rest of the game can be searched, hard plays it out perfectly
+    let empty_cells = game_board.legal_placements().len();
+    if difficulty == Difficulty::Hard && empty_cells <= EXHAUSTIVE_SEARCH_LIMIT {
+        return find_best_move_hard_mode(game_board, player, table);
+    }
+
+    let safe_cells: Vec<Position> = game_board
+        .legal_placements()
+        .into_iter()
+        .filter(|&position| !completes_line(game_board, position, player))
+        .collect();
+
+    // every move loses, so it doesn't matter which one is made
+    if safe_cells.is_empty() {
+        return make_random_move(game_board, rng);
+    }
+
+    let quiet_cells: Vec<Position> = safe_cells
+        .iter()
+        .copied()
+        .filter(|&position| !completes_line(game_board, position, player.opponent()))
+        .collect();
+
+    let candidates = if quiet_cells.is_empty() {
+        safe_cells
+    } else {
+        quiet_cells
+    };
+
+    if difficulty == Difficulty::Medium {
+        return candidates[rng.gen_range(0..candidates.len())];
+    }
+
+    if let Some(mirror) = find_mirror_move(game_board, player) {
+        if candidates.contains(&mirror) {
+            return mirror;
+        }
+    }
+
+    // otherwise keep away from the computer's own pieces as much as possible
+    let mut best = candidates[0];
+    let mut best_count = usize::MAX;
+
+    for position in candidates {
+        let count = count_pieces_in_reach(game_board, position, player.get_board_piece());
+        if count < best_count {
+            best = position;
+            best_count = count;
         }
     }
 
-    let position = make_random_move(game_board);
-    ComputerMove::new(true, position)
+    best
 }
 
-fn player_has_opposite_corners(game_board: &[[BoardPiece; 3]; 3]) -> bool {
-    let player_corners = vec![
-        Position::new(0, 0),
-        Position::new(0, 2),
-        Position::new(2, 0),
-        Position::new(2, 2),
-    ];
+// Lines can't cross sub-board edges in ultimate, so rather than scanning the whole grid
+// every legal move is scored on what it does to its own sub-board, and on hard also on
+// which sub-board it sends the player to
+fn ultimate_computer_move(
+    game_board: &Board,
+    player: Player,
+    difficulty: Difficulty,
+    rng: &mut GameRng,
+) -> Position {
+    if difficulty == Difficulty::Easy {
+        return make_random_move(game_board, rng);
+    }
+
+    let mut best_score = i32::MIN;
+    let mut best_moves = Vec::new();
+
+    for position in game_board.legal_placements() {
+        let score = score_ultimate_move(game_board, position, player, difficulty);
+
+        if score > best_score {
+            best_score = score;
+            best_moves.clear();
+        }
+
+        if score == best_score {
+            best_moves.push(position);
+        }
+    }
+
+    best_moves[rng.gen_range(0..best_moves.len())]
+}
+
+fn score_ultimate_move(
+    game_board: &Board,
+    position: Position,
+    player: Player,
+    difficulty: Difficulty,
+) -> i32 {
+    let sub_board = game_board.sub_board_of(position);
+
+    let mut next_board = game_board.clone();
+    next_board.place(position, player);
+
+    if let GameState::Win(outcome) = next_board.outcome() {
+        if outcome.get_winner() == player {
+            return 10_000;
+        }
+    }
+
+    let mut score = 0;
+
+    if next_board.sub_board_winner(sub_board) == Some(player) {
+        score += 100;
+    }
+
+    let mut blocked_board = game_board.clone();
+    blocked_board.place(position, player.opponent());
+
+    if blocked_board.sub_board_winner(sub_board) == Some(player.opponent()) {
+        score += 80;
+    }
+
+    if difficulty != Difficulty::Hard {
+        return score;
+    }
+
+    // a decided target sub-board lets the player go anywhere
+    if next_board.active_sub_board().is_none() {
+        score -= 30;
+    }
+
+    for reply in next_board.legal_placements() {
+        let mut reply_board = next_board.clone();
+        reply_board.place(reply, player.opponent());
+
+        if let GameState::Win(outcome) = reply_board.outcome() {
+            if outcome.get_winner() == player.opponent() {
+                return -10_000;
+            }
+        }
 
-    for i in 0..3 {
-        if game_board[player_corners[i].get_y() as usize][player_corners[i].get_x() as usize]
-            == BoardPiece::X
-            && game_board[player_corners[(i + 2) % 4].get_y() as usize]
-                [player_corners[(i + 2) % 4].get_x() as usize]
-                == BoardPiece::X
+        if reply_board.sub_board_winner(reply_board.sub_board_of(reply)) == Some(player.opponent())
         {
-            return true;
+            score -= 90;
+            break;
         }
     }
 
-    false
+    // the center of a sub-board is in the most lines, then the corners
+    let local_y = position.get_y() % SUB_BOARD_SIZE;
+    let local_x = position.get_x() % SUB_BOARD_SIZE;
+
+    score
+        + match (local_y, local_x) {
+            (1, 1) => 3,
+            (1, _) | (_, 1) => 1,
+            _ => 2,
+        }
 }
 
-fn get_opposite_corner(corner: Position) -> Position {
-    Position::new(2 - corner.get_y(), 2 - corner.get_x())
+// Qubic lines also run between the layers, with gravity only the lowest empty cell of
+// each column can be played, and in infinite a line can fall apart as its oldest mark
+// vanishes, so wins and blocks are found by trying every legal move instead of scanning
+// the rows, columns and diagonals for gaps
+fn line_scoring_computer_move(
+    game_board: &Board,
+    player: Player,
+    difficulty: Difficulty,
+    rng: &mut GameRng,
+) -> Position {
+    if difficulty == Difficulty::Easy {
+        return make_random_move(game_board, rng);
+    }
+
+    let legal_moves = game_board.legal_placements();
+
+    for side in [player, player.opponent()] {
+        for &position in &legal_moves {
+            if completes_line(game_board, position, side) {
+                return position;
+            }
+        }
+    }
+
+    if difficulty == Difficulty::Medium {
+        return make_random_move(game_board, rng);
+    }
+
+    let mut best = legal_moves[0];
+    let mut best_score = i32::MIN;
+
+    for position in legal_moves {
+        let mut score = score_cell_lines(game_board, position, player);
+
+        // dropping a piece opens up the cell above it, which shouldn't be one the
+        // player wins with
+        if gives_away_win(game_board, position, player) {
+            score -= 10_000;
+        }
+
+        if score > best_score {
+            best = position;
+            best_score = score;
+        }
+    }
+
+    best
 }
 
-fn make_random_move(game_board: &[[BoardPiece; 3]; 3]) -> Position {
-    loop {
-        let y = rand::thread_rng().gen_range(0..3);
-        let x = rand::thread_rng().gen_range(0..3);
+// Each open line through the cell is worth more the more pieces one side already has in
+// it, so the player builds up their own lines while cutting off the opponent's. Making two
+// threats at once (or stopping the opponent from doing so) outweighs everything else
+fn score_cell_lines(game_board: &Board, position: Position, player: Player) -> i32 {
+    let almost_won = game_board.get_win_length() as u32 - 2;
+
+    let mut score = 0;
+    let mut own_threats = 0;
+    let mut opponent_threats = 0;
 
-        let current_pos = Position::new(y, x);
+    for line in game_board.lines_through(position) {
+        let own = count_in_line(game_board, line, player.get_board_piece());
+        let opponent = count_in_line(game_board, line, player.opponent().get_board_piece());
 
-        if valid_move(&game_board, current_pos.clone()) {
-            return current_pos;
+        if opponent == 0 {
+            score += 4_i32.pow(own);
+            if own == almost_won {
+                own_threats += 1;
+            }
         }
+
+        if own == 0 {
+            score += 3_i32.pow(opponent);
+            if opponent == almost_won {
+                opponent_threats += 1;
+            }
+        }
+    }
+
+    if own_threats >= 2 {
+        score += 1000;
+    }
+
+    if opponent_threats >= 2 {
+        score += 500;
     }
+
+    score
 }
 
-// Checks if a player will win the game on the current turn. if they can, it returns the position to block them
-// Also used to check if the computer can win the game on the current turn, by using block position as win position
-fn counter_player_win(game_board: &[[BoardPiece; 3]; 3], win_player: BoardPiece) -> ComputerMove {
-    match win_player {
-        BoardPiece::X => {}
-        BoardPiece::O => {}
-        _ => panic!("Invalid player piece"),
+fn gives_away_win(game_board: &Board, position: Position, player: Player) -> bool {
+    if game_board.get_variant() != Variant::Gravity {
+        return false;
     }
 
-    // check rows
-    for row in 0..3 {
-        let row_type = match row {
-            0 => Row::Top,
-            1 => Row::Middle,
-            2 => Row::Bottom,
-            _ => panic!("Invalid row"),
-        };
+    let mut next_board = game_board.clone();
+    next_board.place(position, player);
 
-        let line = Board::get_row(game_board, row_type);
+    let above = Position::new(position.get_y() - 1, position.get_x());
+    next_board.valid_move(above)
+        && next_board.landing_position(above) == above
+        && completes_line(&next_board, above, player.opponent())
+}
 
-        let mut player_piece_count = 0;
-        let mut empty_piece_count = 0;
+fn count_in_line(game_board: &Board, line: &[Position], piece: BoardPiece) -> u32 {
+    line.iter()
+        .filter(|&&position| game_board.cell(position) == piece)
+        .count() as u32
+}
 
-        for piece in line {
-            if piece == win_player {
-                player_piece_count += 1;
-            } else if piece == BoardPiece::Empty {
-                empty_piece_count += 1;
+// On an odd sized board the player who holds the center can copy every move of the
+// opponent through the center, and can never be the first to complete a line
+fn find_mirror_move(game_board: &Board, player: Player) -> Option<Position> {
+    let height = game_board.get_height() as i8;
+    let width = game_board.get_width() as i8;
+    let middle = Position::new(height / 2, width / 2);
+
+    if height % 2 == 0 || width % 2 == 0 {
+        return None;
+    }
+
+    if game_board.get_turn() == 0 {
+        return Some(middle);
+    }
+
+    if game_board.cell(middle) != player.get_board_piece() {
+        return None;
+    }
+
+    let mut unanswered = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let position = Position::new(y, x);
+            let mirror = Position::new(height - 1 - y, width - 1 - x);
+
+            if game_board.cell(position) == player.opponent().get_board_piece()
+                && game_board.valid_move(mirror)
+            {
+                unanswered.push(mirror);
             }
         }
+    }
 
-        if player_piece_count == 2 && empty_piece_count == 1 {
-            let empty_position = get_empty_position_from_line(line);
-            if empty_position.is_some() {
-                return ComputerMove::new(true, Position::new(row as i8, empty_position.unwrap()));
-            }
+    if unanswered.len() == 1 {
+        Some(unanswered[0])
+    } else {
+        None
+    }
+}
+
+fn completes_line(game_board: &Board, position: Position, player: Player) -> bool {
+    let mut next_board = game_board.clone();
+    next_board.place(position, player);
+
+    matches!(next_board.outcome(), GameState::Win(_))
+}
+
+// counts the pieces that share a line with position and are close enough to be part of a win
+fn count_pieces_in_reach(game_board: &Board, position: Position, piece: BoardPiece) -> usize {
+    game_board
+        .cells_in_lines_through(position)
+        .into_iter()
+        .filter(|&other| game_board.cell(other) == piece)
+        .count()
+}
+
+// Scores are from the point of view of the player to move. A win is worth more the sooner
+// it happens, so the computer goes for the quickest win and drags out a loss for as long
+// as it can
+const WIN_SCORE: i32 = 1_000_000;
+
+// Boards with at most this many empty cells are searched all the way to the end of the game
+const EXHAUSTIVE_SEARCH_LIMIT: usize = 10;
+
+// on bigger boards the search stops after a few moves and only looks at the most promising cells
+const SEARCH_DEPTH: u32 = 3;
+const SEARCH_WIDTH: usize = 8;
+
+// how many moves ahead hard looks once the pieces slide in morris
+const MORRIS_SEARCH_DEPTH: u32 = 16;
+
+// the 3x3 board has been solved ahead of time, everything else is searched
+pub fn find_best_move_hard_mode(
+    game_board: &Board,
+    player: Player,
+    table: &mut TranspositionTable,
+) -> Position {
+    let optimal_moves = tablebase().optimal_moves(game_board, player);
+    if let Some(&best) = optimal_moves.as_ref().and_then(|moves| moves.first()) {
+        return best;
+    }
+
+    search_best_move(game_board, player, table)
+}
+
+// Negamax with alpha-beta pruning from the computer's point of view. On a 3x3 board the
+// whole game tree is searched, so the computer never loses from any position. Positions
+// already scored are looked up in the table, which is kept for the rest of the game
+pub fn search_best_move(
+    game_board: &Board,
+    player: Player,
+    table: &mut TranspositionTable,
+) -> Position {
+    let candidates = search_candidates(game_board, player);
+    let depth = search_depth(game_board);
+
+    // a fork takes more moves to play out than a shallow search looks ahead, so when the
+    // search can't reach the end of the game, forks are looked for first
+    if depth < game_board.legal_placements().len() as u32 {
+        let urgent = [player, player.opponent()]
+            .iter()
+            .find_map(|&side| game_board.winning_moves(side).first().copied())
+            .or_else(|| make_fork(game_board, player))
+            .or_else(|| block_fork(game_board, player));
+
+        if let Some(urgent) = urgent {
+            return urgent.get_to();
         }
     }
 
-    // check columns
-    for column in 0..3 {
-        let column_type = match column {
-            0 => Column::Left,
-            1 => Column::Middle,
-            2 => Column::Right,
-            _ => panic!("Invalid column"),
+    let mut best = candidates[0];
+    let mut alpha = -WIN_SCORE - 1;
+
+    for position in candidates {
+        let mut next_board = game_board.clone();
+        next_board.place(position, player);
+
+        let score = if completed_line_through(&next_board, position) {
+            completed_line_score(game_board, 1)
+        } else {
+            -negamax(
+                &next_board,
+                player.opponent(),
+                depth - 1,
+                -WIN_SCORE - 1,
+                -alpha,
+                1,
+                table,
+            )
         };
 
-        let line = Board::get_column(game_board, column_type);
+        if score > alpha {
+            best = position;
+            alpha = score;
+        }
+    }
 
-        let mut player_piece_count = 0;
-        let mut empty_piece_count = 0;
+    best
+}
 
-        for piece in line {
-            if piece == win_player {
-                player_piece_count += 1;
-            } else if piece == BoardPiece::Empty {
-                empty_piece_count += 1;
-            }
+// what a position is worth to the side to move when both sides play perfectly
+#[derive(Clone, Copy, PartialEq)]
+pub enum GameValue {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl GameValue {
+    pub fn get_name(&self) -> String {
+        match self {
+            GameValue::Win => "win".to_string(),
+            GameValue::Draw => "draw".to_string(),
+            GameValue::Loss => "loss".to_string(),
         }
+    }
 
-        if player_piece_count == 2 && empty_piece_count == 1 {
-            let empty_position = get_empty_position_from_line(line);
-            if empty_position.is_some() {
-                return ComputerMove::new(
-                    true,
-                    Position::new(empty_position.unwrap(), column as i8),
-                );
-            }
+    // the same position from the other side's point of view
+    pub fn flip(&self) -> GameValue {
+        match self {
+            GameValue::Win => GameValue::Loss,
+            GameValue::Draw => GameValue::Draw,
+            GameValue::Loss => GameValue::Win,
         }
     }
+}
 
-    // check diagonals
-    let line = Board::get_diagonal(game_board, Diagonal::TopLeftToBottomRight);
+// Searches the position to the end of the game for player, who is to move. Only classic
+// and misere boards with few enough empty cells to search exhaustively can be solved
+pub fn solve_position(
+    game_board: &Board,
+    player: Player,
+    table: &mut TranspositionTable,
+) -> Option<GameValue> {
+    match game_board.outcome() {
+        GameState::Win(outcome) if outcome.get_winner() == player => return Some(GameValue::Win),
+        GameState::Win(_) => return Some(GameValue::Loss),
+        GameState::Draw => return Some(GameValue::Draw),
+        GameState::Running => {}
+    }
 
-    let mut player_piece_count = 0;
-    let mut empty_piece_count = 0;
+    if let Some(value) = tablebase().value(game_board, player) {
+        return Some(value);
+    }
+
+    let empty_cells = game_board.legal_placements().len();
+
+    let searchable = matches!(game_board.get_variant(), Variant::Classic | Variant::Misere);
+
+    if !searchable || empty_cells > EXHAUSTIVE_SEARCH_LIMIT {
+        return None;
+    }
+
+    let score = negamax(
+        game_board,
+        player,
+        empty_cells as u32,
+        -WIN_SCORE - 1,
+        WIN_SCORE + 1,
+        0,
+        table,
+    );
 
-    for piece in line {
-        if piece == win_player {
-            player_piece_count += 1;
-        } else if piece == BoardPiece::Empty {
-            empty_piece_count += 1;
+    Some(match score {
+        score if score > WIN_SCORE / 2 => GameValue::Win,
+        score if score < -WIN_SCORE / 2 => GameValue::Loss,
+        _ => GameValue::Draw,
+    })
+}
+
+fn negamax(
+    game_board: &Board,
+    player: Player,
+    depth: u32,
+    mut alpha: i32,
+    mut beta: i32,
+    ply: i32,
+    table: &mut TranspositionTable,
+) -> i32 {
+    // in morris the pieces slide once they're all down, and a side that can't slide loses
+    let candidates: Vec<Move> = if game_board.in_movement_phase(player) {
+        let slides = game_board.legal_slides(player);
+
+        if slides.is_empty() {
+            return -(WIN_SCORE - ply);
         }
+
+        slides
+    } else {
+        search_candidates(game_board, player)
+            .into_iter()
+            .map(Move::place)
+            .collect()
+    };
+
+    if candidates.is_empty() {
+        return 0;
     }
 
-    if player_piece_count == 2 && empty_piece_count == 1 {
-        let empty_position = get_empty_position_from_line(line);
-        if empty_position.is_some() {
-            return ComputerMove::new(
-                true,
-                Position::new(empty_position.unwrap(), empty_position.unwrap()),
-            );
+    // past the search depth the best cell's score stands in for how good the position is,
+    // a slide leaves a cell behind as well so there it's taken as even
+    if depth == 0 {
+        return match candidates[0].get_from() {
+            Some(_) => 0,
+            None => score_cell_lines(game_board, candidates[0].get_to(), player),
+        };
+    }
+
+    let key = canonical_hash(game_board, player);
+    let original_alpha = alpha;
+
+    if let Some(entry) = table.get(key) {
+        if entry.get_depth() >= depth {
+            let score = score_from_table(entry.get_score(), ply);
+
+            match entry.get_bound() {
+                Bound::Exact => return score,
+                Bound::Lower => alpha = alpha.max(score),
+                Bound::Upper => beta = beta.min(score),
+            }
+
+            if alpha >= beta {
+                return score;
+            }
         }
     }
 
-    let line = Board::get_diagonal(game_board, Diagonal::TopRightToBottomLeft);
+    let mut best_score = -WIN_SCORE - 1;
 
-    let mut player_piece_count = 0;
-    let mut empty_piece_count = 0;
+    for candidate in candidates {
+        let mut next_board = game_board.clone();
+        next_board.play(candidate, player);
 
-    for piece in line {
-        if piece == win_player {
-            player_piece_count += 1;
-        } else if piece == BoardPiece::Empty {
-            empty_piece_count += 1;
+        // only a line through the cell just played can have been completed
+        let score = if completed_line_through(&next_board, candidate.get_to()) {
+            completed_line_score(game_board, ply + 1)
+        } else {
+            -negamax(
+                &next_board,
+                player.opponent(),
+                depth - 1,
+                -beta,
+                -alpha,
+                ply + 1,
+                table,
+            )
+        };
+
+        best_score = best_score.max(score);
+        alpha = alpha.max(score);
+
+        if alpha >= beta {
+            break;
         }
     }
 
-    if player_piece_count == 2 && empty_piece_count == 1 {
-        let empty_position = get_empty_position_from_line(line);
-        if empty_position.is_some() {
-            return ComputerMove::new(
-                true,
-                Position::new(empty_position.unwrap(), 2 - empty_position.unwrap()),
-            );
+    let bound = if best_score <= original_alpha {
+        Bound::Upper
+    } else if best_score >= beta {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    };
+
+    table.insert(
+        key,
+        TableEntry::new(depth, score_for_table(best_score, ply), bound),
+    );
+
+    best_score
+}
+
+// completing a line wins the game, except in misere where it loses it
+fn completed_line_score(game_board: &Board, ply: i32) -> i32 {
+    match game_board.get_variant() {
+        Variant::Misere => -(WIN_SCORE - ply),
+        _ => WIN_SCORE - ply,
+    }
+}
+
+// Win and loss scores count the moves from the root of the search, but a position can be
+// reached at a different depth later on, so the table keeps them counted from the position
+fn score_for_table(score: i32, ply: i32) -> i32 {
+    if score > WIN_SCORE / 2 {
+        score + ply
+    } else if score < -WIN_SCORE / 2 {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    if score > WIN_SCORE / 2 {
+        score - ply
+    } else if score < -WIN_SCORE / 2 {
+        score + ply
+    } else {
+        score
+    }
+}
+
+fn completed_line_through(game_board: &Board, position: Position) -> bool {
+    let piece = game_board.cell(position);
+
+    game_board
+        .lines_through(position)
+        .iter()
+        .any(|line| line.iter().all(|&cell| game_board.cell(cell) == piece))
+}
+
+// a piece right next to position on one of its lines, so across layers too, but never
+// across the edge of a sub-board
+fn has_neighbouring_piece(game_board: &Board, position: Position) -> bool {
+    game_board
+        .cells_in_lines_through(position)
+        .into_iter()
+        .any(|other| {
+            let distance = (other.get_z() - position.get_z())
+                .abs()
+                .max((other.get_y() - position.get_y()).abs())
+                .max((other.get_x() - position.get_x()).abs());
+
+            distance == 1 && game_board.cell(other) != BoardPiece::Empty
+        })
+}
+
+fn search_depth(game_board: &Board) -> u32 {
+    let empty_cells = game_board.legal_placements().len();
+
+    if empty_cells <= EXHAUSTIVE_SEARCH_LIMIT {
+        empty_cells as u32
+    } else {
+        SEARCH_DEPTH
+    }
+}
+
+// The legal moves with the best looking ones first, which lets alpha-beta cut off more of
+// the tree. On boards that are too big to search exhaustively only the best few cells next
+// to pieces already on the board are kept
+fn search_candidates(game_board: &Board, player: Player) -> Vec<Position> {
+    let mut legal_moves = game_board.legal_placements();
+
+    if legal_moves.len() > EXHAUSTIVE_SEARCH_LIMIT && game_board.get_turn() > 0 {
+        legal_moves.retain(|&position| has_neighbouring_piece(game_board, position));
+    }
+
+    let mut scored: Vec<(i32, Position)> = legal_moves
+        .into_iter()
+        .map(|position| (score_cell_lines(game_board, position, player), position))
+        .collect();
+
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+
+    if scored.len() > EXHAUSTIVE_SEARCH_LIMIT {
+        scored.truncate(SEARCH_WIDTH);
+    }
+
+    scored.into_iter().map(|(_, position)| position).collect()
+}
+
+fn make_random_move(game_board: &Board, rng: &mut GameRng) -> Position {
+    let legal_moves = game_board.legal_placements();
+    let index = rng.gen_range(0..legal_moves.len());
+
+    legal_moves[index]
+}
+
+// Checks if a player will win the game on the current turn. if they can, it returns the position to block them
+// Also used to check if the computer can win the game on the current turn, by using block position as win position
+fn counter_player_win(game_board: &Board, win_player: BoardPiece) -> ComputerMove {
+    match win_player {
+        BoardPiece::X => {}
+        BoardPiece::O => {}
+        _ => panic!("Invalid player piece"),
+    }
+
+    for line in game_board.winning_lines() {
+        if let Some(empty_position) = get_winning_gap_in_line(game_board, line, win_player) {
+            return ComputerMove::new(true, empty_position);
         }
     }
 
-    ComputerMove::new(false, Position::new(1, 1))
+    ComputerMove::new(false, Position::new(0, 0))
 }
 
-// assumes already checked that 1 empty piece in the row
-fn get_empty_position_from_line(line: [BoardPiece; 3]) -> Option<i8> {
-    for (index, piece) in line.iter().enumerate() {
-        if piece == &BoardPiece::Empty {
-            return Some(index as i8);
+// a line with a single empty cell and the rest owned by win_player, returning the empty cell
+fn get_winning_gap_in_line(
+    game_board: &Board,
+    line: &[Position],
+    win_player: BoardPiece,
+) -> Option<Position> {
+    let mut gaps = line
+        .iter()
+        .filter(|&&position| game_board.cell(position) != win_player);
+
+    match (gaps.next(), gaps.next()) {
+        (Some(&gap), None) if game_board.cell(gap) == BoardPiece::Empty => Some(gap),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded_rng;
+
+    // plays every move the opponent could make against hard, and counts the games hard loses
+    fn hard_losses(
+        game_board: &Board,
+        hard: Player,
+        table: &mut TranspositionTable,
+        rng: &mut GameRng,
+    ) -> usize {
+        match game_board.outcome() {
+            GameState::Win(outcome) if outcome.get_winner() == hard => return 0,
+            GameState::Win(_) => return 1,
+            GameState::Draw => return 0,
+            GameState::Running => {}
         }
+
+        let side_to_move = game_board.side_to_move();
+
+        let replies = if side_to_move == hard {
+            vec![computer_move(game_board, hard, Difficulty::Hard, table, rng)]
+        } else {
+            game_board.legal_moves()
+        };
+
+        replies
+            .into_iter()
+            .map(|reply| {
+                let mut next_board = game_board.clone();
+                next_board.play(reply, side_to_move);
+
+                hard_losses(&next_board, hard, table, rng)
+            })
+            .sum()
     }
 
-    None
+    #[test]
+    fn hard_never_loses_misere() {
+        for first_player in [Player::X, Player::O] {
+            for hard in [Player::X, Player::O] {
+                let game_board = Board::new(3, 3)
+                    .set_variant(Variant::Misere)
+                    .set_first_player(first_player);
+
+                let mut table = TranspositionTable::new();
+                let mut rng = seeded_rng(0);
+
+                assert_eq!(hard_losses(&game_board, hard, &mut table, &mut rng), 0);
+            }
+        }
+    }
 }
@@ -1,30 +1,33 @@
-use crossterm::{cursor, terminal, ExecutableCommand};
+use crossterm::{cursor, style::Stylize, terminal, ExecutableCommand};
 use std::io;
 
-use crate::{Board, BoardPiece, Player, Position};
+use crate::{
+    analysis::{analyse_game, find_turning_point, MoveLabel},
+    board::SUB_BOARD_SIZE,
+    history::MoveHistory,
+    Board, BoardPiece, GameOutcome, GameState, Move, Player, Position, Variant,
+};
 
-pub fn display_board(game_board: &[[BoardPiece; 3]; 3]) {
-    let mut rows = Vec::new();
-    for row in game_board {
-        rows.push(build_row_display(row));
-    }
-
-    println!("{}", rows[0]);
-    println!("------------");
-    println!("{}", rows[1]);
-    println!("------------");
-    println!("{}", rows[2]);
+pub fn display_board(game_board: &Board) {
+    print_cells(game_board, &cell_displays(game_board));
 }
 
+// `picked_up` is the piece being moved in the movement phase of morris
 pub fn display_selector_board(
-    game_board: &[[BoardPiece; 3]; 3],
+    game_board: &Board,
     current_pos: Position,
     current_player: Player,
+    picked_up: Option<Position>,
 ) {
-    let y = current_pos.get_y();
-    let x = current_pos.get_x();
-
-    let marker: BoardPiece = if game_board[y as usize][x as usize] == BoardPiece::Empty {
+    let current_pos = game_board.landing_position(current_pos);
+    let valid = match picked_up {
+        Some(from) => game_board.valid_slide(from, current_pos),
+        None if game_board.in_movement_phase(current_player) => {
+            game_board.cell(current_pos) == current_player.get_board_piece()
+        }
+        None => game_board.valid_move(current_pos),
+    };
+    let marker: BoardPiece = if valid {
         match current_player {
             Player::X => BoardPiece::XSelected,
             Player::O => BoardPiece::OSelected,
@@ -33,13 +36,147 @@ pub fn display_selector_board(
         BoardPiece::Taken
     };
 
-    let new_game_board = Board::place_marker(game_board, current_pos, marker);
-    display_board(&new_game_board);
+    // the selector is only drawn, it never becomes part of the game board
+    let mut layers = cell_displays(game_board);
+    if let Some(from) = picked_up {
+        let cell = cell_display_at(&mut layers, from);
+        *cell = cell.as_str().yellow().to_string();
+    }
+    *cell_display_at(&mut layers, current_pos) = marker.get_board_piece();
+    print_cells(game_board, &layers);
+}
+
+pub fn display_winning_board(game_board: &Board, outcome: &GameOutcome) {
+    let mut layers = cell_displays(game_board);
+
+    // in misere the completed line belongs to the loser
+    for position in outcome.get_line() {
+        let cell = cell_display_at(&mut layers, *position);
+        *cell = match game_board.get_variant() {
+            Variant::Misere => cell.as_str().red().bold().to_string(),
+            _ => cell.as_str().green().bold().to_string(),
+        };
+    }
+
+    print_cells(game_board, &layers);
+}
+
+pub fn display_move_history(history: &MoveHistory) {
+    println!();
+    println!("Seed: {}", history.get_seed());
+    println!("Moves played:");
+
+    let multiple_layers = history.current().get_layers() > 1;
+
+    for (i, entry) in history.moves().iter().enumerate() {
+        let played = format_move(entry.get_move(), multiple_layers);
+
+        println!(
+            "  {}. {} {}",
+            i + 1,
+            entry.get_player().get_player_piece(),
+            played
+        );
+    }
+}
+
+// Every move labelled by what it did to the value of the position with perfect play,
+// followed by the move where the losing side gave the game away
+pub fn display_analysis(history: &MoveHistory) {
+    let analysis = analyse_game(history);
+    let multiple_layers = history.current().get_layers() > 1;
+
+    println!("Analysis:");
+
+    for (i, (entry, result)) in history.moves().iter().zip(&analysis).enumerate() {
+        let played = format_move(entry.get_move(), multiple_layers);
+        let player = entry.get_player().get_player_piece();
+
+        let verdict = match result {
+            None => "too early to solve".to_string(),
+            Some(result) if result.get_label() == MoveLabel::Best => {
+                format!("best, keeps the {}", result.get_after().get_name())
+            }
+            Some(result) => format!(
+                "{}, {} -> {}",
+                result.get_label().get_name(),
+                result.get_before().get_name(),
+                result.get_after().get_name()
+            ),
+        };
+
+        println!("  {}. {} {}: {}", i + 1, player, played, verdict);
+    }
+
+    println!();
+
+    // in a drawn game whoever blundered last got away with it
+    let turning_point = match history.current().outcome() {
+        GameState::Win(_) => find_turning_point(&analysis),
+        _ => return,
+    };
+
+    match turning_point.map(|i| (i, &history.moves()[i], analysis[i])) {
+        Some((i, entry, Some(result))) => {
+            println!(
+                "{} threw away the {} with move {}.",
+                entry.get_player().get_player_piece(),
+                result.get_before().get_name(),
+                i + 1
+            );
+
+            if let Some(better_move) = result.get_better_move() {
+                println!(
+                    "{} would have kept it.",
+                    format_move(better_move, multiple_layers)
+                );
+            }
+        }
+        _ => println!(
+            "The losing side had no draw to throw away in the moves that could be solved."
+        ),
+    }
+}
+
+fn format_move(player_move: Move, multiple_layers: bool) -> String {
+    let to = format_position(player_move.get_to(), multiple_layers);
+
+    // slides in morris show where the piece came from as well
+    match player_move.get_from() {
+        Some(from) => format!("{} -> {}", format_position(from, multiple_layers), to),
+        None => to,
+    }
+}
+
+fn format_position(position: Position, multiple_layers: bool) -> String {
+    if multiple_layers {
+        format!(
+            "(layer {}, {}, {})",
+            position.get_z() + 1,
+            position.get_y(),
+            position.get_x()
+        )
+    } else {
+        format!("({}, {})", position.get_y(), position.get_x())
+    }
 }
 
-// clears the last 5 lines (the amount of lines the board takes up)
-pub fn clear_board() {
-    for _ in 0..5 {
+// a line of text shown under the board, like the reason for a hint
+pub fn display_status_line(status: &str) {
+    println!("{}", status);
+}
+
+pub fn clear_status_line() {
+    io::stdout().execute(cursor::MoveUp(1)).unwrap();
+    io::stdout()
+        .execute(terminal::Clear(terminal::ClearType::CurrentLine))
+        .unwrap();
+}
+
+// clears the lines the board takes up, a row per cell plus a separator between each row,
+// and a title line above boards with more than one layer
+pub fn clear_board(game_board: &Board) {
+    for _ in 0..board_height(game_board) {
         io::stdout().execute(cursor::MoveUp(1)).unwrap();
         io::stdout()
             .execute(terminal::Clear(terminal::ClearType::CurrentLine))
@@ -47,13 +184,117 @@ pub fn clear_board() {
     }
 }
 
-fn build_row_display(board_row: &[BoardPiece; 3]) -> String {
-    let mut row_objects = Vec::new();
+fn board_height(game_board: &Board) -> usize {
+    let title = if game_board.get_layers() > 1 { 1 } else { 0 };
+
+    game_board.get_height() * 2 - 1 + title
+}
+
+// the display of each cell, indexed by layer, row and column
+fn cell_displays(game_board: &Board) -> Vec<Vec<Vec<String>>> {
+    let mut layers = Vec::new();
+
+    for z in 0..game_board.get_layers() {
+        let mut rows = Vec::new();
+
+        for (y, row) in game_board.layer_rows(z).iter().enumerate() {
+            let mut row_displays = Vec::new();
+
+            for (x, board_piece) in row.iter().enumerate() {
+                let position = Position::new_3d(z as i8, y as i8, x as i8);
+
+                let display = if game_board.get_variant() == Variant::Ultimate {
+                    ultimate_cell_display(game_board, position)
+                } else if game_board.next_to_vanish() == Some(position) {
+                    board_piece.get_board_piece().dark_grey().to_string()
+                } else {
+                    board_piece.get_board_piece()
+                };
+
+                row_displays.push(display);
+            }
+
+            rows.push(row_displays);
+        }
+
+        layers.push(rows);
+    }
+
+    layers
+}
+
+fn cell_display_at(layers: &mut [Vec<Vec<String>>], position: Position) -> &mut String {
+    &mut layers[position.get_z() as usize][position.get_y() as usize][position.get_x() as usize]
+}
+
+// Won sub-boards are filled with the winner's marker, and the empty cells of the
+// sub-boards that can be played in are highlighted
+fn ultimate_cell_display(game_board: &Board, position: Position) -> String {
+    let sub_board = game_board.sub_board_of(position);
 
-    for board_piece in board_row {
-        row_objects.push(board_piece.get_board_piece());
+    if let Some(winner) = game_board.sub_board_winner(sub_board) {
+        return match winner {
+            Player::X => winner.get_board_piece().get_board_piece().blue().to_string(),
+            Player::O => winner.get_board_piece().get_board_piece().magenta().to_string(),
+        };
     }
 
-    let formatted_row = format!("{}|{}|{}", row_objects[0], row_objects[1], row_objects[2]);
-    formatted_row
+    if game_board.valid_move(position) {
+        return " · ".yellow().to_string();
+    }
+
+    game_board.cell(position).get_board_piece()
+}
+
+// layers are printed side by side, each with a title once there is more than one
+fn print_cells(game_board: &Board, layers: &[Vec<Vec<String>>]) {
+    let width = game_board.get_width() * 4 - 1;
+    let gap = "     ";
+
+    if layers.len() > 1 {
+        let titles: Vec<String> = (0..layers.len())
+            .map(|z| format!("{:^width$}", format!("Layer {}", z + 1), width = width))
+            .collect();
+        println!("{}", titles.join(gap));
+    }
+
+    let separator = vec!["-".repeat(width); layers.len()].join(gap);
+    let sub_board_separator = vec!["=".repeat(width); layers.len()].join(gap);
+
+    for y in 0..game_board.get_height() {
+        if y > 0 {
+            if is_sub_board_edge(game_board, y) {
+                println!("{}", sub_board_separator);
+            } else {
+                println!("{}", separator);
+            }
+        }
+
+        let row_displays: Vec<String> = layers
+            .iter()
+            .map(|rows| build_row_display(game_board, &rows[y]))
+            .collect();
+        println!("{}", row_displays.join(gap));
+    }
+}
+
+fn build_row_display(game_board: &Board, row_objects: &[String]) -> String {
+    let mut row_display = String::new();
+
+    for (i, row_object) in row_objects.iter().enumerate() {
+        if i > 0 {
+            if is_sub_board_edge(game_board, i) {
+                row_display.push('‖');
+            } else {
+                row_display.push('|');
+            }
+        }
+        row_display.push_str(row_object);
+    }
+
+    row_display
+}
+
+fn is_sub_board_edge(game_board: &Board, index: usize) -> bool {
+    game_board.get_variant() == Variant::Ultimate && index.is_multiple_of(SUB_BOARD_SIZE as usize)
 }
@@ -10,6 +10,12 @@ pub struct OptionSelect {
     options: Vec<String>,
 }
 
+impl Default for OptionSelect {
+    fn default() -> Self {
+        OptionSelect::new()
+    }
+}
+
 impl OptionSelect {
     pub fn new() -> Self {
         OptionSelect {
@@ -42,34 +48,29 @@ impl OptionSelect {
             }
             terminal::enable_raw_mode().expect("Failed to enable raw mode");
 
-            let event = read().unwrap();
-            match event {
-                Event::Key(KeyEvent {
-                    code,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => match code {
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = read().unwrap()
+            {
+                match code {
                     KeyCode::Char('q') => {
                         terminal::disable_raw_mode().unwrap();
                         std::process::exit(0);
                     }
                     KeyCode::Up => {
-                        if current_option > 0 {
-                            current_option -= 1;
-                        }
+                        current_option = current_option.saturating_sub(1);
                     }
-                    KeyCode::Down => {
-                        if current_option < self.options.len() - 1 {
-                            current_option += 1;
-                        }
+                    KeyCode::Down if current_option < self.options.len() - 1 => {
+                        current_option += 1;
                     }
                     KeyCode::Enter => {
                         terminal::disable_raw_mode().expect("Failed to disable raw mode");
                         return self.options[current_option].to_string();
                     }
                     _ => {}
-                },
-                _ => {}
+                }
             }
             terminal::disable_raw_mode().expect("Failed to disable raw mode");
             clear_lines(self.options.len() as u16);
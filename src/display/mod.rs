@@ -2,9 +2,13 @@ pub mod game;
 pub mod inputs;
 
 use core::panic;
+use std::time::Duration;
 
-use self::inputs::OptionSelect;
-use crate::{Difficulty, GameConfig, GameMode};
+use self::{game::display_analysis, inputs::OptionSelect};
+use crate::{
+    computer::MAX_STRENGTH, history::MoveHistory, mcts::SearchBudget, Difficulty, GameConfig,
+    GameMode, Player, StartingPlayer, Variant,
+};
 
 pub fn display_welcome() {
     println!("==============================");
@@ -12,24 +16,27 @@ pub fn display_welcome() {
     println!("      Created by cqb13        ");
     println!("      GitHub: github.com/cqb13");
     println!("==============================");
-    print!("\n");
+    println!();
 
     println!("Controls:");
     println!("  Move with arrows (←↑↓→)");
-    println!("  Enter to select");
+    println!("  Tab / Shift+Tab to switch layers (qubic)");
+    println!("  Enter to select, Esc to put a picked up piece back (morris)");
+    println!("  'u' to undo, 'r' to redo");
+    println!("  'h' for a hint");
     println!("  'q' to quit");
-    print!("\n");
+    println!();
 
     println!("Instructions:");
     println!("  Use arrows to navigate the board.");
     println!("  Press Enter to place your marker.");
-    println!("  Try to get three in a row horizontally, vertically, or diagonally.");
-    println!("  First to three wins!");
-    print!("\n");
+    println!("  Try to get a line horizontally, vertically, or diagonally.");
+    println!("  First to complete a line of the chosen length wins!");
+    println!();
 
     println!("Enjoy the game!");
     println!("==============================");
-    print!("\n");
+    println!();
 }
 
 pub fn game_options() -> GameConfig {
@@ -39,45 +46,375 @@ pub fn game_options() -> GameConfig {
         .set_title("Game Options".to_string())
         .add_option("Play against a friend".to_string())
         .add_option("Play against the computer".to_string())
+        .add_option("Watch the computer play itself".to_string())
+        .add_option("Simulate games between computers".to_string())
         .ask();
 
-    print!("\n");
+    println!();
 
     match option.as_str() {
         "Play against a friend" => {}
         "Play against the computer" => {
             config.set_game_mode(GameMode::SinglePlayer);
-            let difficulty = difficulty_options();
-            match difficulty.as_str() {
-                "Easy" => {}
-                "Medium" => {
-                    config.set_difficulty(Difficulty::Medium);
+            config.set_difficulty(difficulty_options("Difficulty Options", true));
+
+            if config.difficulty == Difficulty::MonteCarlo {
+                println!();
+                config.set_search_budget(search_budget_options(true));
+            }
+
+            println!();
+
+            let side = side_options();
+            match side.as_str() {
+                "Play as X" => {}
+                "Play as O" => {
+                    config.set_human_player(Player::O);
+                }
+                _ => {
+                    panic!("Invalid side selected");
+                }
+            }
+
+            println!();
+
+            let starting_player = starting_player_options();
+            match starting_player.as_str() {
+                "You" => {}
+                "The computer" => {
+                    config.set_starting_player(StartingPlayer::Computer);
+                }
+                "Random" => {
+                    config.set_starting_player(StartingPlayer::Random);
                 }
-                "Hard" => {
-                    config.set_difficulty(Difficulty::Hard);
+                "Take turns each game" => {
+                    config.set_starting_player(StartingPlayer::Alternate);
                 }
                 _ => {
-                    panic!("Invalid difficulty selected");
+                    panic!("Invalid starting player selected");
                 }
             }
         }
+        "Watch the computer play itself" => {
+            config.set_game_mode(GameMode::Spectate);
+            let x_difficulty = difficulty_options("X Difficulty", false);
+            println!();
+            let o_difficulty = difficulty_options("O Difficulty", false);
+            config.set_computer_difficulties(x_difficulty, o_difficulty);
+
+            if [x_difficulty, o_difficulty].contains(&Difficulty::MonteCarlo) {
+                println!();
+                config.set_search_budget(search_budget_options(true));
+            }
+
+            println!();
+            config.set_move_delay(move_delay_options());
+        }
+        "Simulate games between computers" => {
+            config.set_game_mode(GameMode::Simulate);
+            config.set_simulated_games(simulated_games_options());
+            println!();
+
+            match simulated_pairings_options().as_str() {
+                "Every pairing of Easy, Medium and Hard" => config.set_all_pairings(true),
+                "Choose the two difficulties" => {
+                    println!();
+                    let x_difficulty = difficulty_options("X Difficulty", false);
+                    println!();
+                    let o_difficulty = difficulty_options("O Difficulty", false);
+                    config.set_computer_difficulties(x_difficulty, o_difficulty);
+
+                    // a time limit per move would make a batch of games take hours
+                    if [x_difficulty, o_difficulty].contains(&Difficulty::MonteCarlo) {
+                        println!();
+                        config.set_search_budget(search_budget_options(false));
+                    }
+                }
+                _ => panic!("Invalid pairings selected"),
+            }
+        }
         _ => {
             panic!("Invalid game mode selected");
         }
     }
 
+    println!();
+
+    let variant = variant_options();
+    match variant.as_str() {
+        "Classic" => {}
+        "Misere (completing a line loses)" => {
+            config.set_variant(Variant::Misere);
+        }
+        "Ultimate (nine boards in one)" => {
+            config.set_variant(Variant::Ultimate);
+        }
+        "Qubic (4x4x4 cube)" => {
+            config.set_variant(Variant::Qubic);
+        }
+        "Gravity (pieces drop down columns)" => {
+            config.set_variant(Variant::Gravity);
+        }
+        "Infinite (your oldest mark vanishes)" => {
+            config.set_variant(Variant::Infinite);
+        }
+        "Three Men's Morris (move pieces after placing three)" => {
+            config.set_variant(Variant::Morris);
+        }
+        _ => {
+            panic!("Invalid variant selected");
+        }
+    }
+
+    match config.variant {
+        // ultimate, qubic and morris have a fixed board
+        Variant::Ultimate | Variant::Qubic | Variant::Morris => {}
+        Variant::Gravity => {
+            println!();
+
+            let (width, height) = gravity_board_size_options();
+            config.set_board_size(width, height);
+
+            println!();
+            config.set_win_length(win_length_options(width.max(height)));
+        }
+        Variant::Classic | Variant::Misere | Variant::Infinite => {
+            println!();
+
+            let (width, height) = board_size_options();
+            config.set_board_size(width, height);
+
+            // a 3x3 board only has room for three in a row
+            if width > 3 {
+                println!();
+                config.set_win_length(win_length_options(width));
+            } else {
+                config.set_win_length(width);
+            }
+        }
+    }
+
+    println!();
     println!("==============================");
-    print!("\n");
+    println!();
     config
 }
 
-fn difficulty_options() -> String {
-    let option = OptionSelect::new()
-        .set_title("Difficulty Options".to_string())
+// adaptive follows the human's results, so it's only offered when a human is playing
+fn difficulty_options(title: &str, against_human: bool) -> Difficulty {
+    let mut option_select = OptionSelect::new()
+        .set_title(title.to_string())
         .add_option("Easy".to_string())
         .add_option("Medium".to_string())
         .add_option("Hard".to_string())
+        .add_option("Monte Carlo (for big boards)".to_string())
+        .add_option("Custom strength (0-100)".to_string());
+
+    if against_human {
+        option_select = option_select.add_option("Adaptive (follows your results)".to_string());
+    }
+
+    match option_select.ask().as_str() {
+        "Easy" => Difficulty::Easy,
+        "Medium" => Difficulty::Medium,
+        "Hard" => Difficulty::Hard,
+        "Monte Carlo (for big boards)" => Difficulty::MonteCarlo,
+        "Custom strength (0-100)" => {
+            println!();
+            Difficulty::Strength(strength_options())
+        }
+        // starts out as medium
+        "Adaptive (follows your results)" => {
+            Difficulty::Adaptive(Difficulty::Medium.get_strength())
+        }
+        _ => panic!("Invalid difficulty selected"),
+    }
+}
+
+// easy, medium and hard sit at 0, 50 and 100 on the same scale
+fn strength_options() -> u8 {
+    let mut option_select = OptionSelect::new().set_title("Strength".to_string());
+
+    for strength in (0..=MAX_STRENGTH).step_by(10) {
+        option_select = option_select.add_option(format!("{} out of {}", strength, MAX_STRENGTH));
+    }
+
+    match option_select.ask().split(' ').next().map(|strength| strength.parse()) {
+        Some(Ok(strength)) => strength,
+        _ => panic!("Invalid strength selected"),
+    }
+}
+
+fn search_budget_options(allow_time_limit: bool) -> SearchBudget {
+    let mut option_select =
+        OptionSelect::new().set_title("Monte Carlo Thinking Time".to_string());
+
+    // playouts come first, a time limit depends on the machine so it can't be replayed
+    option_select = option_select
+        .add_option("1000 playouts per move".to_string())
+        .add_option("10000 playouts per move".to_string());
+
+    if allow_time_limit {
+        option_select = option_select
+            .add_option("1 second per move".to_string())
+            .add_option("5 seconds per move".to_string());
+    }
+
+    let option = option_select.ask();
+
+    match option.split_once(' ') {
+        Some((amount, unit)) => match (amount.parse(), unit.starts_with("playouts")) {
+            (Ok(playouts), true) => SearchBudget::Iterations(playouts),
+            (Ok(seconds), false) => SearchBudget::Time(Duration::from_secs(seconds as u64)),
+            _ => panic!("Invalid thinking time selected"),
+        },
+        None => panic!("Invalid thinking time selected"),
+    }
+}
+
+fn move_delay_options() -> Duration {
+    let option = OptionSelect::new()
+        .set_title("Time Between Moves".to_string())
+        .add_option("0.25 seconds".to_string())
+        .add_option("0.5 seconds".to_string())
+        .add_option("1 second".to_string())
+        .add_option("2 seconds".to_string())
         .ask();
 
-    option
+    match option.split(' ').next().map(|seconds| seconds.parse()) {
+        Some(Ok(seconds)) => Duration::from_secs_f64(seconds),
+        _ => panic!("Invalid delay selected"),
+    }
+}
+
+fn simulated_pairings_options() -> String {
+    OptionSelect::new()
+        .set_title("Pairings".to_string())
+        .add_option("Every pairing of Easy, Medium and Hard".to_string())
+        .add_option("Choose the two difficulties".to_string())
+        .ask()
+}
+
+fn simulated_games_options() -> usize {
+    let option = OptionSelect::new()
+        .set_title("Games per Pairing".to_string())
+        .add_option("100 games".to_string())
+        .add_option("1000 games".to_string())
+        .add_option("5000 games".to_string())
+        .ask();
+
+    match option.split(' ').next().map(|games| games.parse()) {
+        Some(Ok(games)) => games,
+        _ => panic!("Invalid number of games selected"),
+    }
+}
+
+fn side_options() -> String {
+    OptionSelect::new()
+        .set_title("Your Side".to_string())
+        .add_option("Play as X".to_string())
+        .add_option("Play as O".to_string())
+        .ask()
+}
+
+fn starting_player_options() -> String {
+    OptionSelect::new()
+        .set_title("Who Starts".to_string())
+        .add_option("You".to_string())
+        .add_option("The computer".to_string())
+        .add_option("Random".to_string())
+        .add_option("Take turns each game".to_string())
+        .ask()
+}
+
+// only classic and misere games can be solved, so only they can be analysed
+pub fn play_again(history: &MoveHistory) -> bool {
+    loop {
+        let mut option_select = OptionSelect::new()
+            .set_title("Game Over".to_string())
+            .add_option("Play again".to_string());
+
+        if matches!(
+            history.current().get_variant(),
+            Variant::Classic | Variant::Misere
+        ) {
+            option_select = option_select.add_option("Analyse the game".to_string());
+        }
+
+        let option = option_select.add_option("Quit".to_string()).ask();
+
+        match option.as_str() {
+            "Play again" => return true,
+            "Analyse the game" => {
+                println!();
+                display_analysis(history);
+                println!();
+            }
+            "Quit" => return false,
+            _ => panic!("Invalid option selected"),
+        }
+    }
+}
+
+fn variant_options() -> String {
+    OptionSelect::new()
+        .set_title("Game Rules".to_string())
+        .add_option("Classic".to_string())
+        .add_option("Misere (completing a line loses)".to_string())
+        .add_option("Ultimate (nine boards in one)".to_string())
+        .add_option("Qubic (4x4x4 cube)".to_string())
+        .add_option("Gravity (pieces drop down columns)".to_string())
+        .add_option("Infinite (your oldest mark vanishes)".to_string())
+        .add_option("Three Men's Morris (move pieces after placing three)".to_string())
+        .ask()
+}
+
+fn board_size_options() -> (usize, usize) {
+    let option = OptionSelect::new()
+        .set_title("Board Size".to_string())
+        .add_option("3x3".to_string())
+        .add_option("4x4".to_string())
+        .add_option("5x5".to_string())
+        .add_option("6x6".to_string())
+        .add_option("7x7".to_string())
+        .add_option("10x10".to_string())
+        .add_option("15x15".to_string())
+        .ask();
+
+    parse_board_size(&option)
+}
+
+fn gravity_board_size_options() -> (usize, usize) {
+    let option = OptionSelect::new()
+        .set_title("Board Size (columns x rows)".to_string())
+        .add_option("7x6".to_string())
+        .add_option("6x5".to_string())
+        .add_option("8x7".to_string())
+        .add_option("9x7".to_string())
+        .ask();
+
+    parse_board_size(&option)
+}
+
+fn parse_board_size(option: &str) -> (usize, usize) {
+    match option.split_once('x') {
+        Some((width, height)) => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => panic!("Invalid board size selected"),
+        },
+        None => panic!("Invalid board size selected"),
+    }
+}
+
+fn win_length_options(board_size: usize) -> usize {
+    let mut option_select = OptionSelect::new().set_title("Win Length".to_string());
+
+    for win_length in 3..=board_size.min(6) {
+        option_select = option_select.add_option(format!("{} in a row", win_length));
+    }
+
+    match option_select.ask().split(' ').next().map(|length| length.parse()) {
+        Some(Ok(win_length)) => win_length,
+        _ => panic!("Invalid win length selected"),
+    }
 }
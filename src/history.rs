@@ -0,0 +1,106 @@
+use crate::{Board, Move, Player};
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct HistoryEntry {
+    pub player: Player,
+    pub player_move: Move,
+}
+
+impl HistoryEntry {
+    pub fn new(player: Player, player_move: Move) -> HistoryEntry {
+        HistoryEntry {
+            player,
+            player_move,
+        }
+    }
+
+    pub fn get_player(&self) -> Player {
+        self.player
+    }
+
+    pub fn get_move(&self) -> Move {
+        self.player_move
+    }
+}
+
+// Keeps every board the game has been through, so moves can be taken back and replayed.
+// boards[0] is the starting board and boards[i] is the board after entries[i - 1]
+pub struct MoveHistory {
+    boards: Vec<Board>,
+    entries: Vec<HistoryEntry>,
+    current: usize,
+    // the seed the game's random choices came from
+    seed: u64,
+}
+
+impl MoveHistory {
+    pub fn new(starting_board: Board, seed: u64) -> MoveHistory {
+        MoveHistory {
+            boards: vec![starting_board],
+            entries: Vec::new(),
+            current: 0,
+            seed,
+        }
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn current(&self) -> &Board {
+        &self.boards[self.current]
+    }
+
+    // playing a new move throws away anything that could have been redone
+    pub fn play(&mut self, player_move: Move, player: Player) {
+        let mut game_board = self.current().clone();
+        game_board.play(player_move, player);
+
+        self.boards.truncate(self.current + 1);
+        self.entries.truncate(self.current);
+
+        self.boards.push(game_board);
+        self.entries.push(HistoryEntry::new(player, player_move));
+        self.current += 1;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.current > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.current < self.entries.len()
+    }
+
+    pub fn undo(&mut self) -> Option<HistoryEntry> {
+        if !self.can_undo() {
+            return None;
+        }
+
+        self.current -= 1;
+        Some(self.entries[self.current])
+    }
+
+    pub fn redo(&mut self) -> Option<HistoryEntry> {
+        if !self.can_redo() {
+            return None;
+        }
+
+        self.current += 1;
+        Some(self.entries[self.current - 1])
+    }
+
+    // the move that would be replayed by the next redo
+    pub fn peek_redo(&self) -> Option<HistoryEntry> {
+        self.entries.get(self.current).copied()
+    }
+
+    // the moves leading up to the current board, undone moves are not included
+    pub fn moves(&self) -> &[HistoryEntry] {
+        &self.entries[..self.current]
+    }
+
+    pub fn boards(&self) -> &[Board] {
+        &self.boards[..=self.current]
+    }
+}
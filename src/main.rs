@@ -5,32 +5,83 @@ use crossterm::{
 
 use rand::Rng;
 
+pub mod analysis;
+pub mod board;
 pub mod computer;
 pub mod display;
+pub mod history;
+pub mod mcts;
+pub mod rng;
+pub mod simulation;
+pub mod strategy;
+pub mod tablebase;
+pub mod transposition;
 
 use display::{
     display_welcome,
-    game::{clear_board, display_board, display_selector_board},
-    game_options,
+    game::{
+        clear_board, clear_status_line, display_board, display_move_history,
+        display_selector_board, display_status_line, display_winning_board,
+    },
+    game_options, play_again,
 };
 
-use computer::computer_move;
+use board::{Board, SUB_BOARD_SIZE};
+use computer::{find_hint, ADAPTIVE_STEP, MAX_STRENGTH};
+use history::MoveHistory;
+use mcts::SearchBudget;
+use rng::{
+    game_from_args, random_seed, seed_from_args, seeded_rng, strength_from_args, GameRng,
+};
+use simulation::{side_index, simulate, MAX_GAME_MOVES};
+use std::{thread, time::Duration};
+use strategy::{difficulty_strategy, Strategy};
+use transposition::TranspositionTable;
+
+pub enum PlayerAction {
+    Play(Move),
+    Undo,
+    Redo,
+}
 
 pub enum Movement {
     Up,
     Down,
     Left,
     Right,
+    NextLayer,
+    PreviousLayer,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum GameState {
     Running,
     Draw,
-    Win,
+    Win(GameOutcome),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct GameOutcome {
+    winner: Player,
+    line: Vec<Position>,
 }
 
-#[derive(Clone, Copy)]
+impl GameOutcome {
+    pub fn new(winner: Player, line: Vec<Position>) -> GameOutcome {
+        GameOutcome { winner, line }
+    }
+
+    pub fn get_winner(&self) -> Player {
+        self.winner
+    }
+
+    // the cells that make up the winning row, column or diagonal
+    pub fn get_line(&self) -> &[Position] {
+        &self.line
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum Player {
     X,
     O,
@@ -50,6 +101,13 @@ impl Player {
             Player::O => BoardPiece::O,
         }
     }
+
+    pub fn opponent(&self) -> Player {
+        match self {
+            Player::X => Player::O,
+            Player::O => Player::X,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -63,6 +121,14 @@ pub enum BoardPiece {
 }
 
 impl BoardPiece {
+    pub fn get_player(&self) -> Option<Player> {
+        match self {
+            BoardPiece::X => Some(Player::X),
+            BoardPiece::O => Some(Player::O),
+            _ => None,
+        }
+    }
+
     pub fn get_board_piece(&self) -> String {
         match self {
             BoardPiece::Taken => " ⌧ ".to_string(),
@@ -75,9 +141,41 @@ impl BoardPiece {
     }
 }
 
+#[derive(PartialEq, Clone, Copy)]
 pub enum GameMode {
     SinglePlayer,
     MultiPlayer,
+    // two computers play each other while the human watches
+    Spectate,
+    // two computers play batches of games without a board being shown
+    Simulate,
+}
+
+// who opens each game against the computer
+#[derive(PartialEq, Clone, Copy)]
+pub enum StartingPlayer {
+    Human,
+    Computer,
+    Random,
+    // the opening move goes back and forth from one game to the next
+    Alternate,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Variant {
+    Classic,
+    // completing a line loses instead of wins
+    Misere,
+    // nine sub-boards, winning a sub-board claims its cell on the meta-board
+    Ultimate,
+    // four 4x4 layers stacked into a cube
+    Qubic,
+    // pieces fall to the bottom of the chosen column, like connect four
+    Gravity,
+    // placing a mark past the limit takes the player's oldest mark off the board
+    Infinite,
+    // three men's morris, after three pieces each the players slide them to a neighbouring cell
+    Morris,
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -85,36 +183,58 @@ pub enum Difficulty {
     Easy,
     Medium,
     Hard,
+    // monte carlo tree search, for boards too big for hard to search
+    MonteCarlo,
+    // any point on the 0-100 strength scale that easy, medium and hard are presets of
+    Strength(u8),
+    // a strength that goes up after the human wins a game and down after the human loses one
+    Adaptive(u8),
 }
 
-pub enum Diagonal {
-    TopLeftToBottomRight,
-    TopRightToBottomLeft,
-}
-
-#[derive(Clone, Copy)]
-pub enum Column {
-    Left,
-    Middle,
-    Right,
-}
+impl Difficulty {
+    pub fn get_name(&self) -> String {
+        match self {
+            Difficulty::Easy => "Easy".to_string(),
+            Difficulty::Medium => "Medium".to_string(),
+            Difficulty::Hard => "Hard".to_string(),
+            Difficulty::MonteCarlo => "Monte Carlo".to_string(),
+            Difficulty::Strength(strength) => format!("Strength {}", strength),
+            Difficulty::Adaptive(strength) => format!("Adaptive (strength {})", strength),
+        }
+    }
 
-#[derive(Clone, Copy)]
-pub enum Row {
-    Top,
-    Middle,
-    Bottom,
+    // monte carlo doesn't fit on the scale, it's counted as full strength
+    pub fn get_strength(&self) -> u8 {
+        match self {
+            Difficulty::Easy => 0,
+            Difficulty::Medium => 50,
+            Difficulty::Hard | Difficulty::MonteCarlo => MAX_STRENGTH,
+            Difficulty::Strength(strength) | Difficulty::Adaptive(strength) => {
+                (*strength).min(MAX_STRENGTH)
+            }
+        }
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct Position {
+    pub z: i8,
     pub y: i8,
     pub x: i8,
 }
 
 impl Position {
     pub fn new(y: i8, x: i8) -> Position {
-        Position { y, x }
+        Position { z: 0, y, x }
+    }
+
+    // z picks the layer on three dimensional boards, flat boards only have layer 0
+    pub fn new_3d(z: i8, y: i8, x: i8) -> Position {
+        Position { z, y, x }
+    }
+
+    pub fn set_z(&mut self, z: i8) {
+        self.z = z;
     }
 
     pub fn set_y(&mut self, y: i8) {
@@ -125,6 +245,10 @@ impl Position {
         self.x = x;
     }
 
+    pub fn get_z(&self) -> i8 {
+        self.z
+    }
+
     pub fn get_y(&self) -> i8 {
         self.y
     }
@@ -134,9 +258,50 @@ impl Position {
     }
 }
 
+// a piece placed on `to`, or in the movement phase of morris slid over from `from`
+#[derive(Clone, Copy, PartialEq)]
+pub struct Move {
+    pub from: Option<Position>,
+    pub to: Position,
+}
+
+impl Move {
+    pub fn place(to: Position) -> Move {
+        Move { from: None, to }
+    }
+
+    pub fn slide(from: Position, to: Position) -> Move {
+        Move {
+            from: Some(from),
+            to,
+        }
+    }
+
+    pub fn get_from(&self) -> Option<Position> {
+        self.from
+    }
+
+    pub fn get_to(&self) -> Position {
+        self.to
+    }
+}
+
 pub struct GameConfig {
     pub game_mode: GameMode,
     pub difficulty: Difficulty,
+    pub variant: Variant,
+    pub board_width: usize,
+    pub board_height: usize,
+    pub win_length: usize,
+    pub human_player: Player,
+    pub starting_player: StartingPlayer,
+    pub x_difficulty: Difficulty,
+    pub o_difficulty: Difficulty,
+    pub move_delay: Duration,
+    pub simulated_games: usize,
+    pub all_pairings: bool,
+    pub search_budget: SearchBudget,
+    pub seed: Option<u64>,
 }
 
 impl GameConfig {
@@ -144,6 +309,19 @@ impl GameConfig {
         GameConfig {
             game_mode,
             difficulty,
+            variant: Variant::Classic,
+            board_width: 3,
+            board_height: 3,
+            win_length: 3,
+            human_player: Player::X,
+            starting_player: StartingPlayer::Human,
+            x_difficulty: difficulty,
+            o_difficulty: difficulty,
+            move_delay: Duration::from_millis(500),
+            simulated_games: 100,
+            all_pairings: false,
+            search_budget: SearchBudget::Iterations(1000),
+            seed: None,
         }
     }
 
@@ -154,260 +332,457 @@ impl GameConfig {
     pub fn set_difficulty(&mut self, difficulty: Difficulty) {
         self.difficulty = difficulty;
     }
-}
 
-pub enum BoardPosition {
-    TopLeft,
-    TopMiddle,
-    TopRight,
-    MiddleLeft,
-    MiddleMiddle,
-    MiddleRight,
-    BottomLeft,
-    BottomMiddle,
-    BottomRight,
-}
-
-pub struct Board;
-
-impl Board {
-    pub fn new() -> [[BoardPiece; 3]; 3] {
-        [
-            [BoardPiece::Empty, BoardPiece::Empty, BoardPiece::Empty],
-            [BoardPiece::Empty, BoardPiece::Empty, BoardPiece::Empty],
-            [BoardPiece::Empty, BoardPiece::Empty, BoardPiece::Empty],
-        ]
-    }
-
-    pub fn get_turn(game_board: &[[BoardPiece; 3]; 3]) -> usize {
-        game_board
-            .iter()
-            .flatten()
-            .filter(|&x| *x != BoardPiece::Empty)
-            .count()
-    }
-
-    pub fn place_marker(
-        game_board: &[[BoardPiece; 3]; 3],
-        current_pos: Position,
-        current_player_board_piece: BoardPiece,
-    ) -> [[BoardPiece; 3]; 3] {
-        let y = current_pos.get_y();
-        let x = current_pos.get_x();
-
-        let mut new_game_board = *game_board;
-        new_game_board[y as usize][x as usize] = current_player_board_piece;
-        new_game_board
-    }
-
-    pub fn get_board_piece_at_position(
-        game_board: &[[BoardPiece; 3]; 3],
-        position: BoardPosition,
-    ) -> BoardPiece {
-        match position {
-            BoardPosition::TopLeft => game_board[0][0],
-            BoardPosition::TopMiddle => game_board[0][1],
-            BoardPosition::TopRight => game_board[0][2],
-            BoardPosition::MiddleLeft => game_board[1][0],
-            BoardPosition::MiddleMiddle => game_board[1][1],
-            BoardPosition::MiddleRight => game_board[1][2],
-            BoardPosition::BottomLeft => game_board[2][0],
-            BoardPosition::BottomMiddle => game_board[2][1],
-            BoardPosition::BottomRight => game_board[2][2],
-        }
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
     }
 
-    pub fn get_row(game_board: &[[BoardPiece; 3]; 3], row: Row) -> [BoardPiece; 3] {
-        match row {
-            Row::Top => game_board[0],
-            Row::Middle => game_board[1],
-            Row::Bottom => game_board[2],
-        }
+    pub fn set_board_size(&mut self, board_width: usize, board_height: usize) {
+        self.board_width = board_width;
+        self.board_height = board_height;
     }
 
-    pub fn get_column(game_board: &[[BoardPiece; 3]; 3], column: Column) -> [BoardPiece; 3] {
-        let mut column_array = [BoardPiece::Empty, BoardPiece::Empty, BoardPiece::Empty];
+    pub fn set_win_length(&mut self, win_length: usize) {
+        self.win_length = win_length;
+    }
 
-        let column_number = match column {
-            Column::Left => 0,
-            Column::Middle => 1,
-            Column::Right => 2,
-        };
+    pub fn set_human_player(&mut self, human_player: Player) {
+        self.human_player = human_player;
+    }
 
-        for i in 0..3 {
-            column_array[i] = game_board[i][column_number].clone();
-        }
+    pub fn set_starting_player(&mut self, starting_player: StartingPlayer) {
+        self.starting_player = starting_player;
+    }
 
-        column_array
+    // the strategies of the two computers when they play each other
+    pub fn set_computer_difficulties(
+        &mut self,
+        x_difficulty: Difficulty,
+        o_difficulty: Difficulty,
+    ) {
+        self.x_difficulty = x_difficulty;
+        self.o_difficulty = o_difficulty;
     }
 
-    pub fn get_diagonal(game_board: &[[BoardPiece; 3]; 3], diagonal: Diagonal) -> [BoardPiece; 3] {
-        let mut diagonal_array = [BoardPiece::Empty, BoardPiece::Empty, BoardPiece::Empty];
+    pub fn set_move_delay(&mut self, move_delay: Duration) {
+        self.move_delay = move_delay;
+    }
 
-        match diagonal {
-            Diagonal::TopLeftToBottomRight => {
-                diagonal_array[0] = game_board[0][0];
-                diagonal_array[2] = game_board[2][2];
-            }
-            Diagonal::TopRightToBottomLeft => {
-                diagonal_array[0] = game_board[0][2];
-                diagonal_array[2] = game_board[2][0];
-            }
-        }
+    pub fn set_simulated_games(&mut self, simulated_games: usize) {
+        self.simulated_games = simulated_games;
+    }
+
+    // simulate every pairing of the searching difficulties instead of x and o's difficulties
+    pub fn set_all_pairings(&mut self, all_pairings: bool) {
+        self.all_pairings = all_pairings;
+    }
 
-        diagonal_array[1] = game_board[1][1];
+    // how long the monte carlo difficulty thinks about each move
+    pub fn set_search_budget(&mut self, search_budget: SearchBudget) {
+        self.search_budget = search_budget;
+    }
 
-        diagonal_array
+    // without a seed a random one is picked, and printed so the game can be replayed
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
     }
 }
 
 fn main() {
+    let seed = seed_from_args();
+    // games are counted from one on screen and from zero here
+    let replayed_game = game_from_args().map_or(0, |game: u64| game.saturating_sub(1));
+    let replayed_strength = strength_from_args();
+
     display_welcome();
-    let config = game_options();
+    let mut config = game_options();
 
-    let game_mode = config.game_mode;
-    let difficulty = config.difficulty;
+    if let Some(seed) = seed {
+        config.set_seed(seed);
+    }
 
-    let single_player = match game_mode {
-        GameMode::SinglePlayer => true,
-        GameMode::MultiPlayer => false,
-    };
+    if let (Difficulty::Adaptive(_), Some(strength)) = (config.difficulty, replayed_strength) {
+        config.set_difficulty(Difficulty::Adaptive(strength));
+    }
+
+    if config.game_mode == GameMode::Simulate {
+        simulate(&config);
+        return;
+    }
+
+    // a replayed game picks the session up again at the game its seed was printed for
+    let mut game_number = replayed_game;
+    let first_seed = config
+        .seed
+        .map_or_else(random_seed, |seed| seed.wrapping_sub(game_number));
+
+    loop {
+        // every game gets its own seed, so each one can be replayed on its own
+        let game_seed = first_seed.wrapping_add(game_number);
+        let mut rng = seeded_rng(game_seed);
+
+        let computer = match config.game_mode {
+            GameMode::SinglePlayer => {
+                Some(difficulty_strategy(config.difficulty, config.search_budget))
+            }
+            _ => None,
+        };
+
+        let human_first = match config.starting_player {
+            StartingPlayer::Human => true,
+            StartingPlayer::Computer => false,
+            StartingPlayer::Random => random_turn(&mut rng),
+            StartingPlayer::Alternate => game_number.is_multiple_of(2),
+        };
+
+        let first_player = if human_first {
+            config.human_player
+        } else {
+            config.human_player.opponent()
+        };
+
+        let game_board = new_board(&config).set_first_player(first_player);
+        let history = MoveHistory::new(game_board, game_seed);
+
+        // everything that isn't chosen in the menus goes into the replay command
+        let mut replay = format!("--seed {} --game {}", game_seed, game_number + 1);
+        if let Difficulty::Adaptive(strength) = config.difficulty {
+            replay.push_str(&format!(" --strength {}", strength));
+        }
 
-    game_loop(single_player, difficulty);
+        println!(
+            "Game {}, {} starts. Seed: {} (replay with {})",
+            game_number + 1,
+            first_player.get_player_piece(),
+            game_seed,
+            replay
+        );
+        println!();
+
+        let history = match config.game_mode {
+            GameMode::Spectate => {
+                let strategies = [
+                    difficulty_strategy(config.x_difficulty, config.search_budget),
+                    difficulty_strategy(config.o_difficulty, config.search_budget),
+                ];
+                spectate_loop(history, strategies, config.move_delay, &mut rng)
+            }
+            _ => game_loop(history, computer, config.human_player, &mut rng),
+        };
+        display_move_history(&history);
+
+        if let Difficulty::Adaptive(strength) = config.difficulty {
+            let game_state = history.current().outcome();
+            let strength = adapt_strength(strength, &game_state, config.human_player);
+
+            println!();
+            println!("Computer strength for the next game: {}", strength);
+            config.set_difficulty(Difficulty::Adaptive(strength));
+        }
+
+        game_number += 1;
+
+        println!();
+        if !play_again(&history) {
+            break;
+        }
+        println!();
+    }
 
     terminal::disable_raw_mode().expect("Failed to disable raw mode");
 }
 
-fn game_loop(single_player: bool, difficulty: Difficulty) {
-    let mut game_board = Board::new();
-    let mut current_player = Player::X;
+// The adaptive computer gets stronger after the human wins and weaker after the human
+// loses, so over a few games it settles where the human wins about as often as they lose
+fn adapt_strength(strength: u8, game_state: &GameState, human: Player) -> u8 {
+    match game_state {
+        GameState::Win(outcome) if outcome.get_winner() == human => {
+            strength.saturating_add(ADAPTIVE_STEP).min(MAX_STRENGTH)
+        }
+        GameState::Win(_) => strength.saturating_sub(ADAPTIVE_STEP),
+        GameState::Draw | GameState::Running => strength,
+    }
+}
+
+fn new_board(config: &GameConfig) -> Board {
+    match config.variant {
+        Variant::Ultimate => Board::ultimate(),
+        Variant::Qubic => Board::qubic(),
+        Variant::Morris => Board::morris(),
+        Variant::Gravity => {
+            Board::gravity(config.board_width, config.board_height, config.win_length)
+        }
+        _ => Board::new(config.board_width, config.win_length).set_variant(config.variant),
+    }
+}
 
-    display_board(&game_board);
+// Without a computer strategy both players are human, otherwise the computer plays
+// the side the human didn't pick. Whoever's side is to move on the board goes next
+fn game_loop(
+    mut history: MoveHistory,
+    mut computer: Option<Box<dyn Strategy>>,
+    human: Player,
+    rng: &mut GameRng,
+) -> MoveHistory {
+    let computer_player = computer.as_ref().map(|_| human.opponent());
 
-    if single_player {
-        let mut turn = 0;
+    // hints have their own random choices, so asking for one doesn't change the computer's
+    // moves when a seed is replayed
+    let mut hint_table = TranspositionTable::new();
+    let mut hint_rng = seeded_rng(history.get_seed());
 
-        let computer_first = random_turn();
+    display_board(history.current());
 
-        loop {
-            turn += 1;
+    loop {
+        let computer_moved = take_turn(
+            &mut history,
+            computer.as_mut().map(|strategy| strategy.as_mut() as &mut dyn Strategy),
+            human,
+            rng,
+            |game_board, side_to_move| {
+                player_turn(game_board, side_to_move, &mut hint_table, &mut hint_rng)
+            },
+        );
+
+        if computer_moved {
+            clear_board(history.current());
+        }
+        display_board(history.current());
 
-            if computer_first && turn == 1 && difficulty == Difficulty::Hard {
-                game_board = computer_turn(game_board, difficulty.clone());
-                clear_board();
-                display_board(&game_board);
-            }
+        if game_over(history.current(), computer_player) {
+            break;
+        }
+    }
 
-            game_board = player_turn(&game_board, current_player.clone());
-            display_board(&game_board);
+    history
+}
 
-            match check_win(game_board) {
-                GameState::Running => {}
-                GameState::Draw => {
-                    println!("The game ends in a draw!");
-                    break;
-                }
-                GameState::Win => {
-                    println!("Player {} has won the game!", {
-                        current_player.get_player_piece()
-                    });
-                    break;
-                }
-            }
+// Plays the turn of the side to move, and returns whether it was the computer's. The human's
+// action comes from human_action, which reads the keyboard in a game and a script in tests
+fn take_turn(
+    history: &mut MoveHistory,
+    computer: Option<&mut dyn Strategy>,
+    human: Player,
+    rng: &mut GameRng,
+    human_action: impl FnOnce(&Board, Player) -> PlayerAction,
+) -> bool {
+    let side_to_move = history.current().side_to_move();
+    let against_computer = computer.is_some();
+
+    match computer {
+        Some(strategy) if side_to_move != human => {
+            computer_turn(history, strategy, side_to_move, rng);
+            return true;
+        }
+        _ => {}
+    }
 
-            turn += 1;
+    match human_action(history.current(), side_to_move) {
+        PlayerAction::Play(player_move) => history.play(player_move, side_to_move),
+        PlayerAction::Undo if against_computer => undo_player_turn(history, human),
+        PlayerAction::Undo => {
+            history.undo();
+        }
+        PlayerAction::Redo if against_computer => redo_player_turn(history, human),
+        PlayerAction::Redo => {
+            history.redo();
+        }
+    }
 
-            game_board = computer_turn(game_board, difficulty.clone());
-            clear_board();
-            display_board(&game_board);
+    false
+}
 
-            match check_win(game_board) {
-                GameState::Running => {}
-                GameState::Draw => {
-                    println!("The game ends in a draw!");
-                    break;
-                }
-                GameState::Win => {
-                    println!("The computer has won the game!");
-                    break;
-                }
-            }
+// Two computers play each other with a pause between moves so the game can be followed.
+// Games that could go on forever are stopped after MAX_GAME_MOVES
+fn spectate_loop(
+    mut history: MoveHistory,
+    mut strategies: [Box<dyn Strategy>; 2],
+    move_delay: Duration,
+    rng: &mut GameRng,
+) -> MoveHistory {
+    display_board(history.current());
+
+    loop {
+        thread::sleep(move_delay);
+
+        let side_to_move = history.current().side_to_move();
+        let strategy = strategies[side_index(side_to_move)].as_mut();
+
+        computer_turn(&mut history, strategy, side_to_move, rng);
+        clear_board(history.current());
+        display_board(history.current());
+
+        if game_over(history.current(), None) {
+            break;
         }
-    } else {
-        loop {
-            game_board = player_turn(&game_board, current_player);
-            display_board(&game_board);
-
-            match check_win(game_board) {
-                GameState::Running => {}
-                GameState::Draw => {
-                    println!("The game ends in a draw!");
-                    break;
-                }
-                GameState::Win => {
-                    println!("Player {} has won the game!", {
-                        current_player.get_player_piece()
-                    });
-                    break;
-                }
-            }
 
-            current_player = match current_player {
-                Player::X => Player::O,
-                Player::O => Player::X,
+        if history.moves().len() >= MAX_GAME_MOVES {
+            println!("The game is stopped after {} moves.", MAX_GAME_MOVES);
+            break;
+        }
+    }
+
+    history
+}
+
+// against the computer the player's move and the computer's reply are taken back together
+fn undo_player_turn(history: &mut MoveHistory, human: Player) {
+    if !history.moves().iter().any(|entry| entry.get_player() == human) {
+        return;
+    }
+
+    while let Some(entry) = history.undo() {
+        if entry.get_player() == human {
+            break;
+        }
+    }
+}
+
+fn redo_player_turn(history: &mut MoveHistory, human: Player) {
+    if history.redo().is_none() {
+        return;
+    }
+
+    if let Some(entry) = history.peek_redo() {
+        if entry.get_player() != human {
+            history.redo();
+        }
+    }
+}
+
+// announces the result if the game has finished
+fn game_over(game_board: &Board, computer_player: Option<Player>) -> bool {
+    match game_board.outcome() {
+        GameState::Running => false,
+        GameState::Draw => {
+            println!("The game ends in a draw!");
+            true
+        }
+        GameState::Win(outcome) => {
+            clear_board(game_board);
+            display_winning_board(game_board, &outcome);
+
+            if computer_player == Some(outcome.get_winner()) {
+                println!("The computer has won the game!");
+            } else {
+                println!(
+                    "Player {} has won the game!",
+                    outcome.get_winner().get_player_piece()
+                );
             }
+            true
         }
     }
 }
 
-fn random_turn() -> bool {
-    let mut rng = rand::thread_rng();
+fn random_turn(rng: &mut GameRng) -> bool {
     let random_number = rng.gen_range(0..100);
 
-    random_number < 45
+    random_number < 50
 }
 
-fn player_turn(game_board: &[[BoardPiece; 3]; 3], current_player: Player) -> [[BoardPiece; 3]; 3] {
-    let mut current_pos = Position::new(1, 1);
-
-    clear_board();
-    display_selector_board(&game_board, current_pos, current_player);
+// the hint table and rng are only used when the player asks for a hint
+fn player_turn(
+    game_board: &Board,
+    current_player: Player,
+    hint_table: &mut TranspositionTable,
+    hint_rng: &mut GameRng,
+) -> PlayerAction {
+    let (first, last) = cursor_bounds(game_board);
+    let mut current_pos = Position::new(
+        (first.get_y() + last.get_y()) / 2,
+        (first.get_x() + last.get_x()) / 2,
+    );
+
+    // in the movement phase of morris the first enter picks a piece up and the second puts it down
+    let mut picked_up: Option<Position> = None;
+    // the reason for the last hint, shown under the board until the next key press
+    let mut status: Option<String> = None;
+
+    clear_board(game_board);
+    display_selector_board(game_board, current_pos, current_player, picked_up);
 
     loop {
         terminal::enable_raw_mode().expect("Failed to enable raw mode");
-        current_pos = if let Ok(event) = read() {
-            match event {
-                Event::Key(KeyEvent {
-                    code,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => match code {
-                    KeyCode::Char('q') => {
-                        terminal::disable_raw_mode().expect("Failed to disable raw mode");
-                        println!("Quitting...");
-                        std::process::exit(0);
+        let event = read();
+
+        if status.take().is_some() {
+            clear_status_line();
+        }
+
+        current_pos = if let Ok(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        {
+            match code {
+                KeyCode::Char('q') => {
+                    terminal::disable_raw_mode().expect("Failed to disable raw mode");
+                    println!("Quitting...");
+                    std::process::exit(0);
+                }
+                KeyCode::Up => move_current_pos(game_board, current_pos, Movement::Up),
+                KeyCode::Down => move_current_pos(game_board, current_pos, Movement::Down),
+                KeyCode::Left => move_current_pos(game_board, current_pos, Movement::Left),
+                KeyCode::Right => move_current_pos(game_board, current_pos, Movement::Right),
+                KeyCode::Tab => move_current_pos(game_board, current_pos, Movement::NextLayer),
+                KeyCode::BackTab => {
+                    move_current_pos(game_board, current_pos, Movement::PreviousLayer)
+                }
+                KeyCode::Char('u') => {
+                    terminal::disable_raw_mode().expect("Failed to disable raw mode");
+                    clear_board(game_board);
+                    return PlayerAction::Undo;
+                }
+                KeyCode::Char('r') => {
+                    terminal::disable_raw_mode().expect("Failed to disable raw mode");
+                    clear_board(game_board);
+                    return PlayerAction::Redo;
+                }
+                KeyCode::Esc => {
+                    picked_up = None;
+                    current_pos
+                }
+                KeyCode::Char('h') => {
+                    let hint = find_hint(game_board, current_player, hint_table, hint_rng);
+                    let hint_move = hint.get_move();
+                    status = Some(format!("Hint: {}", hint.get_reason()));
+
+                    // a slide is shown with its piece already picked up
+                    picked_up = hint_move.get_from();
+
+                    // with gravity the cursor stays on the top row above the column
+                    match game_board.get_variant() {
+                        Variant::Gravity => Position::new(0, hint_move.get_to().get_x()),
+                        _ => hint_move.get_to(),
                     }
-                    KeyCode::Up => move_current_pos(current_pos, Movement::Up),
-                    KeyCode::Down => move_current_pos(current_pos, Movement::Down),
-                    KeyCode::Left => move_current_pos(current_pos, Movement::Left),
-                    KeyCode::Right => move_current_pos(current_pos, Movement::Right),
-                    KeyCode::Enter => {
-                        terminal::disable_raw_mode().expect("Failed to disable raw mode");
-
-                        if valid_move(&game_board, current_pos) {
-                            clear_board();
-                            let updated_board = Board::place_marker(
-                                &game_board,
-                                current_pos,
-                                current_player.get_board_piece(),
-                            );
-                            terminal::disable_raw_mode().expect("Failed to disable raw mode");
-                            return updated_board;
+                }
+                KeyCode::Enter => {
+                    terminal::disable_raw_mode().expect("Failed to disable raw mode");
+
+                    if game_board.in_movement_phase(current_player) {
+                        match picked_up {
+                            Some(from) if from == current_pos => picked_up = None,
+                            Some(from) if game_board.valid_slide(from, current_pos) => {
+                                clear_board(game_board);
+                                return PlayerAction::Play(Move::slide(from, current_pos));
+                            }
+                            _ if game_board.cell(current_pos)
+                                == current_player.get_board_piece() =>
+                            {
+                                picked_up = Some(current_pos)
+                            }
+                            _ => {}
                         }
-                        current_pos.clone()
+                    } else if game_board.valid_move(current_pos) {
+                        clear_board(game_board);
+                        return PlayerAction::Play(Move::place(
+                            game_board.landing_position(current_pos),
+                        ));
                     }
-                    _ => current_pos,
-                },
+                    current_pos
+                }
                 _ => current_pos,
             }
         } else {
@@ -416,102 +791,198 @@ fn player_turn(game_board: &[[BoardPiece; 3]; 3], current_player: Player) -> [[B
 
         terminal::disable_raw_mode().expect("Failed to disable raw mode");
 
-        clear_board();
-        display_selector_board(&game_board, current_pos, current_player);
+        clear_board(game_board);
+        display_selector_board(game_board, current_pos, current_player, picked_up);
+
+        if let Some(status) = &status {
+            display_status_line(status);
+        }
     }
 }
 
 /**
- *   X ------------------>
- * Y  
- * |  (0,0) | (0,1) | (0,2)
- * |  ---------------------
- * |  (1,0) | (1,1) | (1,2)
- * |  ---------------------
- * V  (2,0) | (2,1) | (2,2)
+ *   X ------------------------------->
+ * Y
+ * |  (0,0)   | (0,1)   | ... | (0,n-1)
+ * |  ---------------------------------
+ * |  (1,0)   | (1,1)   | ... | (1,n-1)
+ * |  ---------------------------------
+ * V  (n-1,0) | (n-1,1) | ... | (n-1,n-1)
  */
-fn move_current_pos(mut current_pos: Position, movement_direction: Movement) -> Position {
+fn move_current_pos(
+    game_board: &Board,
+    mut current_pos: Position,
+    movement_direction: Movement,
+) -> Position {
+    let z = current_pos.get_z();
     let y = current_pos.get_y();
     let x = current_pos.get_x();
+    let (first, last) = cursor_bounds(game_board);
+    let last_layer = game_board.get_layers() as i8 - 1;
 
     match movement_direction {
-        Movement::Up if y > 0 => current_pos.set_y(y - 1),
-        Movement::Up => current_pos.set_y(2),
-        Movement::Down if y < 2 => current_pos.set_y(y + 1),
-        Movement::Down => current_pos.set_y(0),
-        Movement::Left if x > 0 => current_pos.set_x(x - 1),
-        Movement::Left => current_pos.set_x(2),
-        Movement::Right if x < 2 => current_pos.set_x(x + 1),
-        Movement::Right => current_pos.set_x(0),
+        Movement::Up if y > first.get_y() => current_pos.set_y(y - 1),
+        Movement::Up => current_pos.set_y(last.get_y()),
+        Movement::Down if y < last.get_y() => current_pos.set_y(y + 1),
+        Movement::Down => current_pos.set_y(first.get_y()),
+        Movement::Left if x > first.get_x() => current_pos.set_x(x - 1),
+        Movement::Left => current_pos.set_x(last.get_x()),
+        Movement::Right if x < last.get_x() => current_pos.set_x(x + 1),
+        Movement::Right => current_pos.set_x(first.get_x()),
+        Movement::NextLayer if z < last_layer => current_pos.set_z(z + 1),
+        Movement::NextLayer => current_pos.set_z(0),
+        Movement::PreviousLayer if z > 0 => current_pos.set_z(z - 1),
+        Movement::PreviousLayer => current_pos.set_z(last_layer),
     }
 
     current_pos
 }
 
-fn computer_turn(
-    mut game_board: [[BoardPiece; 3]; 3],
-    difficulty: Difficulty,
-) -> [[BoardPiece; 3]; 3] {
-    let computer_move = computer_move(&game_board, difficulty);
+// the top left and bottom right cells the cursor can reach, in ultimate the cursor
+// is kept inside the sub-board that has to be played in
+fn cursor_bounds(game_board: &Board) -> (Position, Position) {
+    if let Some(sub_board) = game_board.active_sub_board() {
+        let top = sub_board.get_y() * SUB_BOARD_SIZE;
+        let left = sub_board.get_x() * SUB_BOARD_SIZE;
+
+        return (
+            Position::new(top, left),
+            Position::new(top + SUB_BOARD_SIZE - 1, left + SUB_BOARD_SIZE - 1),
+        );
+    }
 
-    let updated_board = Board::place_marker(&game_board, computer_move, BoardPiece::O);
-    game_board = updated_board;
+    // with gravity the piece falls down the column, so the cursor only moves sideways
+    let last_row = if game_board.get_variant() == Variant::Gravity {
+        0
+    } else {
+        game_board.get_height() as i8 - 1
+    };
 
-    game_board
+    (
+        Position::new(0, 0),
+        Position::new(last_row, game_board.get_width() as i8 - 1),
+    )
 }
 
-fn valid_move(game_board: &[[BoardPiece; 3]; 3], current_pos: Position) -> bool {
-    let y = current_pos.get_y();
-    let x = current_pos.get_x();
-
-    game_board[y as usize][x as usize] == BoardPiece::Empty
+fn computer_turn(
+    history: &mut MoveHistory,
+    strategy: &mut dyn Strategy,
+    computer: Player,
+    rng: &mut GameRng,
+) {
+    let computer_move = strategy.choose_move(history.current(), computer, rng);
+
+    history.play(computer_move, computer);
 }
 
-fn check_win(game_board: [[BoardPiece; 3]; 3]) -> GameState {
-    let mut lines = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for i in 0..3 {
-        let row = match i {
-            0 => Row::Top,
-            1 => Row::Middle,
-            2 => Row::Bottom,
-            _ => panic!("Invalid row number"),
-        };
+    // Plays a game the way game_loop does, with the human's moves scripted: the first
+    // cell of the script that's still free, and an undo and a redo along the way
+    fn play_scripted_game(seed: u64, difficulty: Difficulty) -> MoveHistory {
+        let mut rng = seeded_rng(seed);
+        let mut computer = difficulty_strategy(difficulty, SearchBudget::Iterations(200));
+        let human = Player::X;
 
-        let column = match i {
-            0 => Column::Left,
-            1 => Column::Middle,
-            2 => Column::Right,
-            _ => panic!("Invalid column number"),
+        let first_player = if random_turn(&mut rng) {
+            human
+        } else {
+            human.opponent()
         };
 
-        lines.push(Board::get_column(&game_board, column));
-        lines.push(Board::get_row(&game_board, row));
+        let game_board = Board::new(3, 3).set_first_player(first_player);
+        let mut history = MoveHistory::new(game_board, seed);
+        let script = [4, 0, 8, 2, 6, 1, 3, 5, 7].map(|cell| Position::new(cell / 3, cell % 3));
+        let mut human_turns = 0;
+
+        while history.current().outcome() == GameState::Running {
+            take_turn(
+                &mut history,
+                Some(computer.as_mut()),
+                human,
+                &mut rng,
+                |game_board, _| {
+                    human_turns += 1;
+
+                    match human_turns {
+                        2 => PlayerAction::Undo,
+                        3 => PlayerAction::Redo,
+                        _ => PlayerAction::Play(Move::place(
+                            script
+                                .into_iter()
+                                .find(|&cell| game_board.valid_move(cell))
+                                .unwrap(),
+                        )),
+                    }
+                },
+            );
+        }
+
+        history
     }
 
-    lines.push(Board::get_diagonal(
-        &game_board,
-        Diagonal::TopLeftToBottomRight,
-    ));
+    #[test]
+    fn same_seed_replays_the_same_game() {
+        for difficulty in [Difficulty::Strength(30), Difficulty::MonteCarlo] {
+            for seed in 0..20 {
+                let first = play_scripted_game(seed, difficulty);
+                let second = play_scripted_game(seed, difficulty);
+
+                assert_eq!(first.moves().len(), second.moves().len());
+                for (first, second) in first.moves().iter().zip(second.moves()) {
+                    assert!(first.get_player() == second.get_player());
+                    assert!(first.get_move() == second.get_move());
+                }
+            }
+        }
+    }
 
-    lines.push(Board::get_diagonal(
-        &game_board,
-        Diagonal::TopRightToBottomLeft,
-    ));
+    #[test]
+    fn undo_and_redo_take_the_computer_reply_along() {
+        let mut rng = seeded_rng(0);
+        let mut computer = difficulty_strategy(Difficulty::Medium, SearchBudget::Iterations(0));
+        let human = Player::X;
+        let mut history = MoveHistory::new(Board::new(3, 3), 0);
+
+        // none when it's the computer's turn, the human isn't asked then
+        let mut turn = |history: &mut MoveHistory, action: Option<PlayerAction>| {
+            take_turn(history, Some(computer.as_mut()), human, &mut rng, |_, _| {
+                action.expect("The human was asked on the computer's turn")
+            });
+        };
 
-    for line in lines {
-        if line[0] == line[1] && line[1] == line[2] && line[0] != BoardPiece::Empty {
-            return GameState::Win;
+        for _ in 0..2 {
+            let cell = history.current().legal_placements()[0];
+            turn(&mut history, Some(PlayerAction::Play(Move::place(cell))));
+            turn(&mut history, None);
         }
-    }
+        let played = history.moves().to_vec();
+        assert_eq!(played.len(), 4);
 
-    for row in game_board {
-        for board_piece in row {
-            if board_piece == BoardPiece::Empty {
-                return GameState::Running;
-            }
+        turn(&mut history, Some(PlayerAction::Undo));
+        assert!(history.moves() == &played[..2]);
+        for entry in &played[2..] {
+            assert!(history.current().cell(entry.get_move().get_to()) == BoardPiece::Empty);
         }
-    }
 
-    GameState::Draw
+        turn(&mut history, Some(PlayerAction::Redo));
+        assert!(history.moves() == &played[..]);
+
+        // a different move after an undo throws away the moves that could have been redone
+        turn(&mut history, Some(PlayerAction::Undo));
+        assert!(history.can_redo());
+        let other_cell = history
+            .current()
+            .legal_placements()
+            .into_iter()
+            .find(|&cell| Move::place(cell) != played[2].get_move())
+            .unwrap();
+        turn(&mut history, Some(PlayerAction::Play(Move::place(other_cell))));
+
+        assert!(!history.can_redo());
+        assert_eq!(history.moves().len(), 3);
+        assert!(history.moves()[2].get_move() == Move::place(other_cell));
+    }
 }
@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::{rng::GameRng, Board, GameState, Move, Player};
+
+// random playouts of games that can go on forever, like infinite and morris, are cut off
+// after this many moves and counted as a draw
+const MAX_PLAYOUT_MOVES: usize = 200;
+
+// how much exploring the tree is favoured over sticking with the moves that have done well
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+// how long the search keeps going before it settles on a move
+#[derive(Clone, Copy, PartialEq)]
+pub enum SearchBudget {
+    Iterations(u32),
+    Time(Duration),
+}
+
+impl SearchBudget {
+    fn is_spent(&self, iterations: u32, started: Instant) -> bool {
+        match self {
+            SearchBudget::Iterations(limit) => iterations >= *limit,
+            SearchBudget::Time(limit) => started.elapsed() >= *limit,
+        }
+    }
+}
+
+struct Node {
+    // the move that led here from the parent, the root has none
+    player_move: Option<Move>,
+    // the player who made that move, wins are counted from their point of view
+    player: Player,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried_moves: Vec<Move>,
+    visits: u32,
+    wins: f64,
+}
+
+impl Node {
+    fn new(
+        player_move: Option<Move>,
+        player: Player,
+        parent: Option<usize>,
+        game_board: &Board,
+    ) -> Node {
+        let untried_moves = match game_board.outcome() {
+            GameState::Running => game_board.legal_moves(),
+            _ => Vec::new(),
+        };
+
+        Node {
+            player_move,
+            player,
+            parent,
+            children: Vec::new(),
+            untried_moves,
+            visits: 0,
+            wins: 0.0,
+        }
+    }
+
+    fn upper_confidence_bound(&self, parent_visits: u32) -> f64 {
+        let visits = self.visits as f64;
+
+        self.wins / visits + EXPLORATION * ((parent_visits as f64).ln() / visits).sqrt()
+    }
+}
+
+// Monte Carlo tree search. Every iteration follows the most promising moves down the
+// tree, adds one new move to it, plays the rest of the game out at random and counts the
+// result for every move on the way. The move that was tried the most is played. It only
+// needs the legal moves and the outcome of a board, so it works for every variant
+pub fn monte_carlo_move(
+    game_board: &Board,
+    player: Player,
+    budget: SearchBudget,
+    rng: &mut GameRng,
+) -> Move {
+    let mut nodes = vec![Node::new(None, player.opponent(), None, game_board)];
+    let started = Instant::now();
+    let mut iterations = 0;
+
+    while !budget.is_spent(iterations, started) {
+        let mut board = game_board.clone();
+        let mut current = 0;
+
+        // selection
+        while nodes[current].untried_moves.is_empty() && !nodes[current].children.is_empty() {
+            let parent_visits = nodes[current].visits;
+
+            current = *nodes[current]
+                .children
+                .iter()
+                .max_by(|&&a, &&b| {
+                    let a = nodes[a].upper_confidence_bound(parent_visits);
+                    let b = nodes[b].upper_confidence_bound(parent_visits);
+                    a.total_cmp(&b)
+                })
+                .unwrap();
+
+            let node = &nodes[current];
+            board.play(node.player_move.unwrap(), node.player);
+        }
+
+        // expansion
+        if !nodes[current].untried_moves.is_empty() {
+            let untried_moves = &mut nodes[current].untried_moves;
+            let player_move = untried_moves.swap_remove(rng.gen_range(0..untried_moves.len()));
+            let mover = board.side_to_move();
+
+            board.play(player_move, mover);
+
+            let child = Node::new(Some(player_move), mover, Some(current), &board);
+            nodes.push(child);
+
+            let child_index = nodes.len() - 1;
+            nodes[current].children.push(child_index);
+            current = child_index;
+        }
+
+        // simulation
+        let winner = play_out(board, rng);
+
+        // backpropagation
+        let mut node_index = Some(current);
+        while let Some(index) = node_index {
+            let node = &mut nodes[index];
+
+            node.visits += 1;
+            node.wins += match winner {
+                Some(winner) if winner == node.player => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+
+            node_index = node.parent;
+        }
+
+        iterations += 1;
+    }
+
+    // without a single iteration any legal move will have to do
+    match nodes[0]
+        .children
+        .iter()
+        .max_by_key(|&&child| nodes[child].visits)
+    {
+        Some(&child) => nodes[child].player_move.unwrap(),
+        None => {
+            let legal_moves = game_board.legal_moves();
+            legal_moves[rng.gen_range(0..legal_moves.len())]
+        }
+    }
+}
+
+// plays random moves until the game is over, none means a draw
+fn play_out(mut game_board: Board, rng: &mut GameRng) -> Option<Player> {
+    for _ in 0..MAX_PLAYOUT_MOVES {
+        match game_board.outcome() {
+            GameState::Win(outcome) => return Some(outcome.get_winner()),
+            GameState::Draw => return None,
+            GameState::Running => {}
+        }
+
+        let legal_moves = game_board.legal_moves();
+        let player_move = legal_moves[rng.gen_range(0..legal_moves.len())];
+        game_board.play(player_move, game_board.side_to_move());
+    }
+
+    None
+}
@@ -0,0 +1,63 @@
+use std::{process, str::FromStr};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::computer::MAX_STRENGTH;
+
+// Every random choice in a game, from who starts to the computer's moves, comes from one
+// of these. Starting it from the same seed and playing the same moves replays the game.
+// The algorithm is fixed, unlike rand's StdRng, so a seed replays the same way on every
+// platform and every version of rand
+pub type GameRng = ChaCha8Rng;
+
+pub fn seeded_rng(seed: u64) -> GameRng {
+    ChaCha8Rng::seed_from_u64(seed)
+}
+
+// used when no seed was asked for, the seed is still printed so the game can be replayed
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+const USAGE: &str = "Usage: tic-tac-toe [--seed <number>] [--game <number>] [--strength <0-100>]";
+
+// reads `--seed <number>` or `--seed=<number>` from the command line
+pub fn seed_from_args() -> Option<u64> {
+    number_from_args("--seed")
+}
+
+// which game of the session, counted from one, the seed was printed for. It decides who
+// starts when the starting player alternates
+pub fn game_from_args() -> Option<u64> {
+    number_from_args("--game")
+}
+
+// the strength the adaptive computer had reached when the game was played
+pub fn strength_from_args() -> Option<u8> {
+    number_from_args("--strength").map(|strength: u8| strength.min(MAX_STRENGTH))
+}
+
+// a malformed number stops the game with the usage instead of a panic
+fn number_from_args<T: FromStr>(flag: &str) -> Option<T> {
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix(flag) {
+            Some("") => args.next(),
+            Some(value) => value.strip_prefix('=').map(|value| value.to_string()),
+            None => continue,
+        };
+
+        match value.map(|value| value.parse()) {
+            Some(Ok(number)) => return Some(number),
+            _ => {
+                eprintln!("Invalid value for {}", flag);
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    None
+}
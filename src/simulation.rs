@@ -0,0 +1,132 @@
+use crate::{
+    new_board,
+    rng::{random_seed, seeded_rng, GameRng},
+    strategy::{difficulty_strategy, Strategy},
+    Board, Difficulty, GameConfig, GameState, Player,
+};
+
+// Games between computers that last this many moves are stopped and counted as a draw.
+// Pieces keep vanishing in infinite and sliding around in morris, so those might never end
+pub const MAX_GAME_MOVES: usize = 200;
+
+// monte carlo is left out, a batch of games takes far too long with it in every pairing
+const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+// where a side's strategy is kept when two computers play each other
+pub fn side_index(player: Player) -> usize {
+    match player {
+        Player::X => 0,
+        Player::O => 1,
+    }
+}
+
+#[derive(Default)]
+pub struct SimulationResult {
+    pub x_wins: usize,
+    pub draws: usize,
+    pub o_wins: usize,
+}
+
+impl SimulationResult {
+    pub fn record(&mut self, game_state: &GameState) {
+        match game_state {
+            GameState::Win(outcome) if outcome.get_winner() == Player::X => self.x_wins += 1,
+            GameState::Win(_) => self.o_wins += 1,
+            GameState::Draw | GameState::Running => self.draws += 1,
+        }
+    }
+
+    pub fn get_x_wins(&self) -> usize {
+        self.x_wins
+    }
+
+    pub fn get_draws(&self) -> usize {
+        self.draws
+    }
+
+    pub fn get_o_wins(&self) -> usize {
+        self.o_wins
+    }
+}
+
+// plays a game between two strategies without showing it, X's strategy comes first
+pub fn play_computer_game(
+    mut game_board: Board,
+    strategies: &mut [Box<dyn Strategy>; 2],
+    rng: &mut GameRng,
+) -> GameState {
+    for _ in 0..MAX_GAME_MOVES {
+        let side_to_move = game_board.side_to_move();
+        let strategy = &mut strategies[side_index(side_to_move)];
+        let player_move = strategy.choose_move(&game_board, side_to_move, rng);
+        game_board.play(player_move, side_to_move);
+
+        let game_state = game_board.outcome();
+        if game_state != GameState::Running {
+            return game_state;
+        }
+    }
+
+    GameState::Draw
+}
+
+// plays the chosen difficulties, or every pairing of them, against each other on the
+// chosen board and reports how the games ended
+pub fn simulate(config: &GameConfig) {
+    let seed = config.seed.unwrap_or_else(random_seed);
+    let mut rng = seeded_rng(seed);
+
+    println!("Seed: {} (replay with --seed {})", seed, seed);
+    println!(
+        "Playing {} games for each pairing...",
+        config.simulated_games
+    );
+    println!();
+
+    let pairings = if config.all_pairings {
+        DIFFICULTIES
+            .iter()
+            .flat_map(|&x_difficulty| {
+                DIFFICULTIES
+                    .iter()
+                    .map(move |&o_difficulty| (x_difficulty, o_difficulty))
+            })
+            .collect()
+    } else {
+        vec![(config.x_difficulty, config.o_difficulty)]
+    };
+
+    for (x_difficulty, o_difficulty) in pairings {
+        let mut strategies = [
+            difficulty_strategy(x_difficulty, config.search_budget),
+            difficulty_strategy(o_difficulty, config.search_budget),
+        ];
+        let mut result = SimulationResult::default();
+
+        for _ in 0..config.simulated_games {
+            let game_state = play_computer_game(new_board(config), &mut strategies, &mut rng);
+            result.record(&game_state);
+        }
+
+        println!(
+            "  {:<11} (X) vs {:<11} (O): X won {}, drawn {}, O won {}",
+            x_difficulty.get_name(),
+            o_difficulty.get_name(),
+            result.get_x_wins(),
+            result.get_draws(),
+            result.get_o_wins()
+        );
+
+        for strategy in &strategies {
+            if let Some(report) = strategy.report() {
+                println!("      {}", report);
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "Games still going after {} moves are counted as draws.",
+        MAX_GAME_MOVES
+    );
+}
@@ -0,0 +1,158 @@
+use rand::Rng;
+
+use crate::{
+    computer::computer_move,
+    mcts::{monte_carlo_move, SearchBudget},
+    rng::{seeded_rng, GameRng},
+    transposition::TranspositionTable,
+    Board, Difficulty, Move, Player,
+};
+
+// Chooses the moves of a computer player. Implement it to plug a different bot into
+// the game loop. Random choices should come from rng so seeded games can be replayed
+pub trait Strategy {
+    fn choose_move(&mut self, game_board: &Board, player: Player, rng: &mut GameRng) -> Move;
+
+    // anything worth knowing about how the strategy has been running, like cache statistics
+    fn report(&self) -> Option<String> {
+        None
+    }
+}
+
+// plays any legal move
+pub struct RandomStrategy;
+
+impl Strategy for RandomStrategy {
+    fn choose_move(&mut self, game_board: &Board, player: Player, rng: &mut GameRng) -> Move {
+        computer_move(
+            game_board,
+            player,
+            Difficulty::Easy,
+            &mut TranspositionTable::new(),
+            rng,
+        )
+    }
+}
+
+// takes a win or blocks the opponent's when there is one, otherwise plays any legal move
+pub struct BlockingStrategy;
+
+impl Strategy for BlockingStrategy {
+    fn choose_move(&mut self, game_board: &Board, player: Player, rng: &mut GameRng) -> Move {
+        computer_move(
+            game_board,
+            player,
+            Difficulty::Medium,
+            &mut TranspositionTable::new(),
+            rng,
+        )
+    }
+}
+
+// searches the game tree on classic boards and scores moves with each variant's heuristics
+// on the rest. The positions it has searched are kept between moves
+#[derive(Default)]
+pub struct HeuristicStrategy {
+    table: TranspositionTable,
+}
+
+impl HeuristicStrategy {
+    pub fn new() -> HeuristicStrategy {
+        HeuristicStrategy::default()
+    }
+
+    pub fn get_table(&self) -> &TranspositionTable {
+        &self.table
+    }
+}
+
+impl Strategy for HeuristicStrategy {
+    fn choose_move(&mut self, game_board: &Board, player: Player, rng: &mut GameRng) -> Move {
+        computer_move(game_board, player, Difficulty::Hard, &mut self.table, rng)
+    }
+
+    fn report(&self) -> Option<String> {
+        if self.table.get_lookups() == 0 {
+            return None;
+        }
+
+        Some(format!(
+            "transposition table: {} positions, {} of {} lookups hit ({:.1}%)",
+            self.table.len(),
+            self.table.get_hits(),
+            self.table.get_lookups(),
+            self.table.get_hit_rate() * 100.0
+        ))
+    }
+}
+
+// Plays at any strength from 0 to 100, where easy, medium and hard sit at 0, 50 and 100.
+// The higher the strength, the more often it plays the move hard would, and otherwise
+// medium's or one of the other moves
+pub struct GradedStrategy {
+    strength: u8,
+    table: TranspositionTable,
+}
+
+impl GradedStrategy {
+    pub fn new(strength: u8) -> GradedStrategy {
+        GradedStrategy {
+            strength,
+            table: TranspositionTable::new(),
+        }
+    }
+
+    pub fn get_strength(&self) -> u8 {
+        self.strength
+    }
+}
+
+impl Strategy for GradedStrategy {
+    fn choose_move(&mut self, game_board: &Board, player: Player, rng: &mut GameRng) -> Move {
+        computer_move(
+            game_board,
+            player,
+            Difficulty::Strength(self.strength),
+            &mut self.table,
+            rng,
+        )
+    }
+}
+
+// Plays out random games from every position it considers and picks the move that won
+// the most of them. The same seed and an iteration budget always give the same moves
+pub struct MonteCarloStrategy {
+    budget: SearchBudget,
+}
+
+impl MonteCarloStrategy {
+    pub fn new(budget: SearchBudget) -> MonteCarloStrategy {
+        MonteCarloStrategy { budget }
+    }
+}
+
+impl Strategy for MonteCarloStrategy {
+    fn choose_move(&mut self, game_board: &Board, player: Player, rng: &mut GameRng) -> Move {
+        // the search gets its own stream, seeded with a single draw, so a time budget only
+        // changes the move it picks and not every random choice that comes after it
+        let mut search_rng = seeded_rng(rng.gen());
+
+        monte_carlo_move(game_board, player, self.budget, &mut search_rng)
+    }
+}
+
+// the search budget is only used by the monte carlo difficulty
+pub fn difficulty_strategy(
+    difficulty: Difficulty,
+    search_budget: SearchBudget,
+) -> Box<dyn Strategy> {
+    match difficulty {
+        Difficulty::Easy => Box::new(RandomStrategy),
+        Difficulty::Medium => Box::new(BlockingStrategy),
+        Difficulty::Hard => Box::new(HeuristicStrategy::new()),
+        Difficulty::MonteCarlo => Box::new(MonteCarloStrategy::new(search_budget)),
+        Difficulty::Strength(strength) | Difficulty::Adaptive(strength) => {
+            Box::new(GradedStrategy::new(strength))
+        }
+    }
+}
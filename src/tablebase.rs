@@ -0,0 +1,310 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use crate::{
+    computer::GameValue, transposition::transform, Board, BoardPiece, GameState, Player, Position,
+    Variant,
+};
+
+const SIZE: i8 = 3;
+
+// built the first time it's needed, it only takes a few thousand positions
+static TABLEBASE: OnceLock<Tablebase> = OnceLock::new();
+
+// a position on the 3x3 board with both sides playing perfectly from it
+pub struct TablebaseEntry {
+    pub value: GameValue,
+    // how many more moves the game lasts, a win is taken as soon as possible and a loss
+    // put off for as long as possible
+    pub moves_left: u8,
+    // the moves that keep the value, as cells of the canonical position
+    pub optimal_moves: Vec<Position>,
+}
+
+impl TablebaseEntry {
+    pub fn get_value(&self) -> GameValue {
+        self.value
+    }
+
+    pub fn get_moves_left(&self) -> u8 {
+        self.moves_left
+    }
+}
+
+// Every position of classic 3x3 tic-tac-toe that can come up from an empty board, whichever
+// side starts, solved once. Rotations and reflections of a position share one entry
+pub struct Tablebase {
+    entries: HashMap<u32, TablebaseEntry>,
+}
+
+pub fn tablebase() -> &'static Tablebase {
+    TABLEBASE.get_or_init(Tablebase::generate)
+}
+
+impl Tablebase {
+    fn generate() -> Tablebase {
+        let mut tablebase = Tablebase {
+            entries: HashMap::new(),
+        };
+
+        for first_player in [Player::X, Player::O] {
+            let game_board =
+                Board::new(SIZE as usize, SIZE as usize).set_first_player(first_player);
+            tablebase.solve(&game_board, first_player);
+        }
+
+        tablebase
+    }
+
+    fn solve(&mut self, game_board: &Board, player: Player) -> (GameValue, u8) {
+        let (key, symmetry) = canonical_key(game_board, player);
+
+        if let Some(entry) = self.entries.get(&key) {
+            return (entry.get_value(), entry.get_moves_left());
+        }
+
+        let mut value = match game_board.outcome() {
+            GameState::Win(outcome) if outcome.get_winner() == player => Some(GameValue::Win),
+            GameState::Win(_) => Some(GameValue::Loss),
+            GameState::Draw => Some(GameValue::Draw),
+            GameState::Running => None,
+        };
+        let mut moves_left = 0;
+        let mut optimal_moves = Vec::new();
+
+        if value.is_none() {
+            let mut results = Vec::new();
+
+            for position in game_board.legal_placements() {
+                let mut next_board = game_board.clone();
+                next_board.place(position, player);
+
+                let (reply_value, reply_moves_left) = self.solve(&next_board, player.opponent());
+                results.push((position, reply_value.flip(), reply_moves_left + 1));
+            }
+
+            let best = results
+                .iter()
+                .map(|&(_, value, moves_left)| score(value, moves_left))
+                .max()
+                .unwrap_or_default();
+
+            for (position, result_value, result_moves_left) in results {
+                if score(result_value, result_moves_left) == best {
+                    value = Some(result_value);
+                    moves_left = result_moves_left;
+                    optimal_moves.push(to_canonical(position, symmetry));
+                }
+            }
+        }
+
+        let value = value.unwrap_or(GameValue::Draw);
+
+        self.entries.insert(
+            key,
+            TablebaseEntry {
+                value,
+                moves_left,
+                optimal_moves,
+            },
+        );
+
+        (value, moves_left)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // only classic 3x3 boards with three in a row to win are in the table
+    pub fn covers(game_board: &Board) -> bool {
+        game_board.get_variant() == Variant::Classic
+            && game_board.get_width() == SIZE as usize
+            && game_board.get_height() == SIZE as usize
+            && game_board.get_layers() == 1
+            && game_board.get_win_length() == SIZE as usize
+    }
+
+    pub fn get(&self, game_board: &Board, player: Player) -> Option<&TablebaseEntry> {
+        if !Tablebase::covers(game_board) {
+            return None;
+        }
+
+        self.entries.get(&canonical_key(game_board, player).0)
+    }
+
+    pub fn value(&self, game_board: &Board, player: Player) -> Option<GameValue> {
+        self.get(game_board, player).map(|entry| entry.get_value())
+    }
+
+    // the optimal moves turned back to the way the board is actually laid out
+    pub fn optimal_moves(&self, game_board: &Board, player: Player) -> Option<Vec<Position>> {
+        let entry = self.get(game_board, player)?;
+        let (_, symmetry) = canonical_key(game_board, player);
+
+        Some(
+            entry
+                .optimal_moves
+                .iter()
+                .map(|&position| from_canonical(position, symmetry))
+                .collect(),
+        )
+    }
+}
+
+// wins first, the quicker the better, then draws, then losses, the later the better
+fn score(value: GameValue, moves_left: u8) -> i32 {
+    match value {
+        GameValue::Win => 100 - moves_left as i32,
+        GameValue::Draw => 0,
+        GameValue::Loss => moves_left as i32 - 100,
+    }
+}
+
+// The cells read in base 3 under each of the eight symmetries, with the side to move on
+// top. The smallest of them is the key, and the symmetry it came from is kept to turn the
+// stored moves back around
+fn canonical_key(game_board: &Board, player: Player) -> (u32, u8) {
+    (0..8)
+        .map(|symmetry| (symmetry_key(game_board, player, symmetry), symmetry))
+        .min()
+        .unwrap_or_default()
+}
+
+fn symmetry_key(game_board: &Board, player: Player, symmetry: u8) -> u32 {
+    let mut key = match player {
+        Player::X => 0,
+        Player::O => 1,
+    };
+
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let position = from_canonical(Position::new(y, x), symmetry);
+
+            key = key * 3
+                + match game_board.cell(position) {
+                    BoardPiece::X => 1,
+                    BoardPiece::O => 2,
+                    _ => 0,
+                };
+        }
+    }
+
+    key
+}
+
+// the cell of the board that a cell of the canonical position was read from
+fn from_canonical(position: Position, symmetry: u8) -> Position {
+    let (y, x) = transform(position.get_y(), position.get_x(), SIZE - 1, symmetry);
+
+    Position::new(y, x)
+}
+
+fn to_canonical(position: Position, symmetry: u8) -> Position {
+    (0..SIZE)
+        .flat_map(|y| (0..SIZE).map(move |x| Position::new(y, x)))
+        .find(|&canonical| from_canonical(canonical, symmetry) == position)
+        .unwrap_or(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        computer::{find_hint, search_best_move},
+        rng::seeded_rng,
+        transposition::TranspositionTable,
+    };
+
+    // Solves the position with a plain minimax that only skips exact repeats, no symmetries,
+    // and checks the table and hard against it on the way. Returns the value and how many
+    // moves the game lasts, with the same preference for quick wins and slow losses
+    fn check_position(
+        game_board: &Board,
+        player: Player,
+        solved: &mut HashMap<u32, (GameValue, u8)>,
+        canonical_keys: &mut HashSet<u32>,
+        table: &mut TranspositionTable,
+    ) -> (GameValue, u8) {
+        let exact_key = symmetry_key(game_board, player, 0);
+
+        if let Some(&result) = solved.get(&exact_key) {
+            return result;
+        }
+
+        let result = match game_board.outcome() {
+            GameState::Win(outcome) if outcome.get_winner() == player => (GameValue::Win, 0),
+            GameState::Win(_) => (GameValue::Loss, 0),
+            GameState::Draw => (GameValue::Draw, 0),
+            GameState::Running => game_board
+                .legal_placements()
+                .into_iter()
+                .map(|position| {
+                    let mut next_board = game_board.clone();
+                    next_board.place(position, player);
+
+                    let (value, moves_left) = check_position(
+                        &next_board,
+                        player.opponent(),
+                        solved,
+                        canonical_keys,
+                        table,
+                    );
+                    (value.flip(), moves_left + 1)
+                })
+                .max_by_key(|&(value, moves_left)| score(value, moves_left))
+                .unwrap(),
+        };
+
+        let entry = tablebase()
+            .get(game_board, player)
+            .expect("Reachable position missing from the tablebase");
+
+        assert!(entry.get_value() == result.0);
+        assert_eq!(entry.get_moves_left(), result.1);
+
+        // the search and the hints have to agree with the table without looking at it
+        if game_board.outcome() == GameState::Running {
+            let optimal_moves = tablebase().optimal_moves(game_board, player).unwrap();
+            let searched_move = search_best_move(game_board, player, table);
+            let hint = find_hint(game_board, player, table, &mut seeded_rng(0));
+
+            assert!(optimal_moves.contains(&searched_move));
+            assert!(optimal_moves.contains(&hint.get_move().get_to()));
+        }
+
+        canonical_keys.insert(canonical_key(game_board, player).0);
+        solved.insert(exact_key, result);
+        result
+    }
+
+    #[test]
+    fn tablebase_matches_minimax_everywhere() {
+        let mut solved = HashMap::new();
+        let mut canonical_keys = HashSet::new();
+        let mut table = TranspositionTable::new();
+
+        for first_player in [Player::X, Player::O] {
+            let game_board =
+                Board::new(SIZE as usize, SIZE as usize).set_first_player(first_player);
+            let (value, _) = check_position(
+                &game_board,
+                first_player,
+                &mut solved,
+                &mut canonical_keys,
+                &mut table,
+            );
+
+            assert!(value == GameValue::Draw);
+        }
+
+        // every entry is a reachable position, and every reachable position has an entry
+        assert!(!tablebase().is_empty());
+        assert_eq!(tablebase().len(), canonical_keys.len());
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use crate::{Board, BoardPiece, Player, Position};
+
+// how a stored score relates to the real value of the position, alpha-beta cutoffs mean
+// a search often only proves the score is at least or at most some value
+#[derive(Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+pub struct TableEntry {
+    pub depth: u32,
+    pub score: i32,
+    pub bound: Bound,
+}
+
+impl TableEntry {
+    pub fn new(depth: u32, score: i32, bound: Bound) -> TableEntry {
+        TableEntry {
+            depth,
+            score,
+            bound,
+        }
+    }
+
+    // how many moves deep the position was searched
+    pub fn get_depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn get_score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn get_bound(&self) -> Bound {
+        self.bound
+    }
+}
+
+// Scores of positions the search has already been through, so reaching them again by a
+// different order of moves, or as a rotation or reflection of a known position, is free
+#[derive(Default)]
+pub struct TranspositionTable {
+    entries: HashMap<u64, TableEntry>,
+    lookups: u64,
+    hits: u64,
+}
+
+impl TranspositionTable {
+    pub fn new() -> TranspositionTable {
+        TranspositionTable::default()
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<TableEntry> {
+        self.lookups += 1;
+
+        let entry = self.entries.get(&key).copied();
+        if entry.is_some() {
+            self.hits += 1;
+        }
+
+        entry
+    }
+
+    pub fn insert(&mut self, key: u64, entry: TableEntry) {
+        self.entries.insert(key, entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get_lookups(&self) -> u64 {
+        self.lookups
+    }
+
+    pub fn get_hits(&self) -> u64 {
+        self.hits
+    }
+
+    // the share of lookups that found a stored position, from 0 to 1
+    pub fn get_hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            return 0.0;
+        }
+
+        self.hits as f64 / self.lookups as f64
+    }
+}
+
+// The same hash for every rotation and reflection of the board, taken as the smallest hash
+// of the eight ways the cells can be read. Boards that aren't square only have the one
+pub fn canonical_hash(game_board: &Board, player: Player) -> u64 {
+    let symmetries = if game_board.get_width() == game_board.get_height() {
+        8
+    } else {
+        1
+    };
+
+    (0..symmetries)
+        .map(|symmetry| symmetry_hash(game_board, player, symmetry))
+        .min()
+        .unwrap_or_default()
+}
+
+fn symmetry_hash(game_board: &Board, player: Player, symmetry: u8) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let height = game_board.get_height() as i8;
+    let width = game_board.get_width() as i8;
+
+    player.get_player_piece().hash(&mut hasher);
+
+    for z in 0..game_board.get_layers() as i8 {
+        for y in 0..height {
+            for x in 0..width {
+                let (from_y, from_x) = transform(y, x, width - 1, symmetry);
+                let cell = game_board.cell(Position::new_3d(z, from_y, from_x));
+
+                hasher.write_u8(match cell {
+                    BoardPiece::X => 1,
+                    BoardPiece::O => 2,
+                    _ => 0,
+                });
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+// the four rotations, then the four rotations of the mirrored board
+pub fn transform(y: i8, x: i8, last: i8, symmetry: u8) -> (i8, i8) {
+    match symmetry {
+        0 => (y, x),
+        1 => (x, last - y),
+        2 => (last - y, last - x),
+        3 => (last - x, y),
+        4 => (y, last - x),
+        5 => (x, y),
+        6 => (last - y, x),
+        _ => (last - x, last - y),
+    }
+}